@@ -0,0 +1,153 @@
+use crate::float2::Float2;
+
+// direction in degree counter clockwise from the x axis, kept in -180.0..180.0
+// so headings never grow past a full turn and a - b is the shortest signed difference
+// rates and field of views stay plain f32, they may be larger than half a turn
+#[derive(Debug, Copy, Clone, PartialEq, Default)]
+pub struct Angle(f32);
+
+impl std::ops::Add for Angle {
+    type Output = Self;
+
+    fn add(self, other: Self) -> Self {
+        Self::from_degrees(self.0 + other.0)
+    }
+}
+
+impl std::ops::Sub for Angle {
+    type Output = Self;
+
+    fn sub(self, other: Self) -> Self {
+        Self::from_degrees(self.0 - other.0)
+    }
+}
+
+impl std::ops::Neg for Angle {
+    type Output = Self;
+
+    fn neg(self) -> Self {
+        Self::from_degrees(-self.0)
+    }
+}
+
+impl std::ops::Mul<f32> for Angle {
+    type Output = Self;
+
+    fn mul(self, other: f32) -> Self {
+        Self::from_degrees(self.0 * other)
+    }
+}
+
+impl std::ops::Div<f32> for Angle {
+    type Output = Self;
+
+    fn div(self, other: f32) -> Self {
+        Self::from_degrees(self.0 / other)
+    }
+}
+
+impl std::ops::AddAssign for Angle {
+    fn add_assign(&mut self, rhs: Self) {
+        *self = *self + rhs;
+    }
+}
+
+impl std::ops::SubAssign for Angle {
+    fn sub_assign(&mut self, rhs: Self) {
+        *self = *self - rhs;
+    }
+}
+
+impl std::fmt::Display for Angle {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{}°", self.0)
+    }
+}
+
+impl Angle {
+    pub const ZERO: Angle = Angle(0.0);
+
+    pub const fn from_degrees(degrees: f32) -> Self {
+        // rem_euclid, spelled out to work in constants
+        let wrapped = (degrees + 180.0) % 360.0;
+        if wrapped < 0.0 {
+            Self(wrapped + 360.0 - 180.0)
+        } else {
+            Self(wrapped - 180.0)
+        }
+    }
+
+    pub fn from_radians(radians: f32) -> Self {
+        Self::from_degrees(radians.to_degrees())
+    }
+
+    pub fn degrees(self) -> f32 {
+        self.0
+    }
+
+    pub fn radians(self) -> f32 {
+        self.0.to_radians()
+    }
+
+    // shortest signed turn from other to self
+    pub fn difference(self, other: Self) -> Self {
+        self - other
+    }
+
+    // size of the turn without its direction, 0.0..=180.0
+    // a plain f32, half a turn doesn't fit into -180.0..180.0 as a positive Angle
+    pub fn abs_degrees(self) -> f32 {
+        self.0.abs()
+    }
+
+    // wrapped angles have no order, -179.0 and 179.0 are 2.0 apart,
+    // so they get compared by the size of the turn between them
+    pub fn approx_eq(self, other: Self, tolerance: Self) -> bool {
+        self.difference(other).abs_degrees() <= tolerance.abs_degrees()
+    }
+
+    // unit vector in this direction
+    pub fn to_vector(self) -> Float2 {
+        let (sin, cos) = self.radians().sin_cos();
+        Float2::new(cos, sin)
+    }
+
+    // turns the short way round, t = 0 -> self; t = 1 -> other
+    pub fn lerp(self, other: Self, t: f32) -> Self {
+        self + (other - self) * t
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn wraps_into_half_turns() {
+        assert_eq!(Angle::from_degrees(190.0).degrees(), -170.0);
+        assert_eq!(Angle::from_degrees(-190.0).degrees(), 170.0);
+        assert_eq!(Angle::from_degrees(720.0).degrees(), 0.0);
+        assert_eq!(Angle::from_degrees(180.0).degrees(), -180.0);
+    }
+
+    #[test]
+    fn compares_across_the_wrap() {
+        let tolerance = Angle::from_degrees(2.5);
+        assert!(Angle::from_degrees(179.0).approx_eq(Angle::from_degrees(-179.0), tolerance));
+        assert!(!Angle::from_degrees(10.0).approx_eq(Angle::from_degrees(5.0), tolerance));
+        assert_eq!(
+            Angle::from_degrees(-179.0)
+                .difference(Angle::from_degrees(179.0))
+                .degrees(),
+            2.0
+        );
+        assert_eq!(Angle::from_degrees(-30.0).abs_degrees(), 30.0);
+        assert_eq!(Angle::from_degrees(180.0).abs_degrees(), 180.0);
+    }
+
+    #[test]
+    fn lerps_the_short_way_round() {
+        let angle = Angle::from_degrees(170.0).lerp(Angle::from_degrees(-170.0), 0.5);
+        assert!(angle.approx_eq(Angle::from_degrees(180.0), Angle::from_degrees(1e-3)));
+    }
+}
@@ -0,0 +1,34 @@
+use crate::pose::Pose;
+use crate::sensor::{Mount, Sensor};
+use crate::units::Length;
+use crate::world::World;
+
+use std::time::Duration;
+
+// the bumper covers the whole round body
+// z_min, z_max = above the floor, low objects below the lidar still hit the bumper
+pub struct Bumper {
+    pub radius: Length,
+    pub z_min: Length,
+    pub z_max: Length,
+}
+
+impl Sensor for Bumper {
+    type Reading = bool;
+
+    fn mount(&self) -> Mount {
+        Mount::center()
+    }
+
+    fn rate(&self) -> f32 {
+        f32::INFINITY
+    }
+
+    fn update(&mut self, world: &World, pose: Pose, _time: Duration) -> Option<bool> {
+        Some(
+            world
+                .geometry()
+                .check_collision(pose.position, self.radius, self.z_min, self.z_max),
+        )
+    }
+}
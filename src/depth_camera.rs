@@ -0,0 +1,102 @@
+use crate::angle::Angle;
+use crate::float2::Float2;
+use crate::lidar::cast_ray;
+use crate::pose::Pose;
+use crate::sensor::{Mount, Sensor};
+use crate::units::Length;
+use crate::world::World;
+
+use std::time::Duration;
+
+// forward facing structured light / tof camera with a dense column array
+// fov, vertical_fov = opening of the image, below half a turn like any Angle
+// rate = Hz
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct DepthCamera {
+    pub mount: Mount,
+    pub fov: Angle,
+    pub columns: usize,
+    pub min_range: Length,
+    pub max_range: Length,
+    pub vertical_fov: Angle,
+    pub rate: f32,
+}
+
+// angles are relative to the camera direction
+// depths = None if the column sees nothing within range
+#[derive(Debug, Clone, PartialEq)]
+pub struct DepthImage {
+    pub angle_min: f32,
+    pub angle_increment: f32,
+    pub depths: Vec<Option<Length>>,
+}
+
+impl DepthCamera {
+    // low in the front bumper, looking straight ahead
+    pub fn front(radius: Length) -> Self {
+        Self {
+            mount: Mount::new(Float2::new(radius.mm() - 20.0, 0.0), Angle::ZERO)
+                .with_height(Length::from_mm(40.0)),
+            fov: Angle::from_degrees(70.0),
+            columns: 160,
+            min_range: Length::from_mm(50.0),
+            max_range: Length::from_mm(1500.0),
+            vertical_fov: Angle::from_degrees(50.0),
+            rate: 15.0,
+        }
+    }
+
+    pub fn angle_increment(&self) -> f32 {
+        self.fov.degrees() / (self.columns.max(2) - 1) as f32
+    }
+
+    pub fn angle(&self, column: usize) -> Angle {
+        -self.fov / 2.0 + Angle::from_degrees(column as f32 * self.angle_increment())
+    }
+
+    // lowest and highest point above the floor the camera sees at a distance
+    // low obstacles below the lidar plane still show up in the lower part of the image
+    pub fn vertical_extent(&self, distance: Length) -> (Length, Length) {
+        let half = distance * (self.vertical_fov / 2.0).radians().tan();
+        let height = self.mount.height;
+        ((height - half).max(Length::ZERO), height + half)
+    }
+}
+
+impl Sensor for DepthCamera {
+    type Reading = DepthImage;
+
+    fn mount(&self) -> Mount {
+        self.mount
+    }
+
+    fn rate(&self) -> f32 {
+        self.rate
+    }
+
+    fn update(&mut self, world: &World, pose: Pose, _time: Duration) -> Option<DepthImage> {
+        let depths = (0..self.columns)
+            .map(|column| {
+                let ray = (pose.heading + self.angle(column)).to_vector();
+                cast_ray(
+                    world,
+                    pose.position,
+                    ray,
+                    self.max_range.mm(),
+                    |wall, distance| {
+                        let (z_min, z_max) = self.vertical_extent(Length::from_mm(distance));
+                        wall.overlaps(z_min, z_max)
+                    },
+                )
+                .map(|hit| hit.distance)
+                .filter(|distance| *distance >= self.min_range)
+            })
+            .collect();
+
+        Some(DepthImage {
+            angle_min: -self.fov.degrees() / 2.0,
+            angle_increment: self.angle_increment(),
+            depths,
+        })
+    }
+}
@@ -0,0 +1,271 @@
+use crate::float2::Float2;
+use crate::pose::Pose;
+use crate::sensor::{Mount, Sensor};
+use crate::world::World;
+
+use std::time::Duration;
+
+// density = g/m²
+// amounts = g
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Surface {
+    Hardfloor,
+    Carpet,
+    Rug,
+}
+
+impl Surface {
+    // fraction of the remaining dirt the suction removes per second
+    // the time a cell spends under the robot depends on the speed,
+    // so slower passes pick up more dirt
+    pub fn pickup_rate(&self) -> f32 {
+        match self {
+            Surface::Hardfloor => 3.0,
+            Surface::Carpet => 1.2,
+            Surface::Rug => 0.6,
+        }
+    }
+
+    // fraction of the wheel motion that turns into actual motion
+    pub fn traction(&self) -> f32 {
+        match self {
+            Surface::Hardfloor => 1.0,
+            Surface::Carpet => 0.95,
+            Surface::Rug => 0.85,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct DirtMap {
+    origin: Float2,
+    cell_size: f32, // mm
+    width: usize,
+    height: usize,
+    dirt: Vec<f32>,
+    surface: Vec<Surface>,
+}
+
+impl DirtMap {
+    pub fn new(origin: Float2, size: Float2, cell_size: f32) -> Self {
+        let width = (size.get_x() / cell_size).ceil() as usize;
+        let height = (size.get_y() / cell_size).ceil() as usize;
+        Self {
+            origin,
+            cell_size,
+            width,
+            height,
+            dirt: vec![0.0; width * height],
+            surface: vec![Surface::Hardfloor; width * height],
+        }
+    }
+
+    // g/m² -> g per cell
+    fn cell_amount(&self, density: f32) -> f32 {
+        density * (self.cell_size / 1000.0).powi(2)
+    }
+
+    fn cell_center(&self, x: usize, y: usize) -> Float2 {
+        self.origin
+            + Float2::new(
+                (x as f32 + 0.5) * self.cell_size,
+                (y as f32 + 0.5) * self.cell_size,
+            )
+    }
+
+    fn cell_index(&self, position: Float2) -> Option<usize> {
+        let local = (position - self.origin) / self.cell_size;
+        if local.get_x() < 0.0 || local.get_y() < 0.0 {
+            return None;
+        }
+        let (x, y) = (local.get_x() as usize, local.get_y() as usize);
+        if x >= self.width || y >= self.height {
+            return None;
+        }
+        Some(y * self.width + x)
+    }
+
+    pub fn add_uniform(&mut self, density: f32) {
+        let amount = self.cell_amount(density);
+        self.dirt.iter_mut().for_each(|cell| *cell += amount);
+    }
+
+    // gaussian shaped cluster, e.g. in front of the kitchen counter
+    pub fn add_hotspot(&mut self, center: Float2, radius: f32, peak_density: f32) {
+        let peak = self.cell_amount(peak_density);
+        for y in 0..self.height {
+            for x in 0..self.width {
+                let dist = (self.cell_center(x, y) - center).length();
+                if dist > radius * 3.0 {
+                    continue;
+                }
+                self.dirt[y * self.width + x] += peak * (-0.5 * (dist / radius).powi(2)).exp();
+            }
+        }
+    }
+
+    // min and max are the corners of an axis aligned rectangle
+    pub fn set_surface(&mut self, min: Float2, max: Float2, surface: Surface) {
+        for y in 0..self.height {
+            for x in 0..self.width {
+                let center = self.cell_center(x, y);
+                if (min.get_x()..=max.get_x()).contains(&center.get_x())
+                    && (min.get_y()..=max.get_y()).contains(&center.get_y())
+                {
+                    self.surface[y * self.width + x] = surface;
+                }
+            }
+        }
+    }
+
+    pub fn density_at(&self, position: Float2) -> f32 {
+        match self.cell_index(position) {
+            Some(index) => self.dirt[index] / self.cell_amount(1.0),
+            None => 0.0,
+        }
+    }
+
+    pub fn surface_at(&self, position: Float2) -> Surface {
+        match self.cell_index(position) {
+            Some(index) => self.surface[index],
+            None => Surface::Hardfloor,
+        }
+    }
+
+    // removes dirt from every cell under the circular footprint
+    // returns the picked up amount
+    pub fn pickup(&mut self, position: Float2, radius: f32, elapsed: &Duration) -> f32 {
+        let mut picked = 0.0;
+        let min = (position - Float2::new(radius, radius) - self.origin) / self.cell_size;
+        let max = (position + Float2::new(radius, radius) - self.origin) / self.cell_size;
+        let x_range =
+            min.get_x().max(0.0) as usize..(max.get_x().max(0.0) as usize + 1).min(self.width);
+        let y_range =
+            min.get_y().max(0.0) as usize..(max.get_y().max(0.0) as usize + 1).min(self.height);
+
+        for y in y_range {
+            for x in x_range.clone() {
+                if (self.cell_center(x, y) - position).length() > radius {
+                    continue;
+                }
+                let index = y * self.width + x;
+                let rate = self.surface[index].pickup_rate();
+                let removed = self.dirt[index] * (1.0 - (-rate * elapsed.as_secs_f32()).exp());
+                self.dirt[index] -= removed;
+                picked += removed;
+            }
+        }
+        picked
+    }
+
+    pub fn total(&self) -> f32 {
+        self.dirt.iter().sum()
+    }
+}
+
+// fires on heavily soiled spots
+// threshold = g/m²
+pub struct DirtSensor {
+    pub threshold: f32,
+}
+
+impl Sensor for DirtSensor {
+    type Reading = bool;
+
+    fn mount(&self) -> Mount {
+        Mount::center()
+    }
+
+    fn rate(&self) -> f32 {
+        f32::INFINITY
+    }
+
+    fn update(&mut self, world: &World, pose: Pose, _time: Duration) -> Option<bool> {
+        Some(world.dirt.lock().unwrap().density_at(pose.position) >= self.threshold)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::angle::Angle;
+
+    #[test]
+    fn surfaces_cover_the_rectangle() {
+        let mut dirt = DirtMap::new(Float2::ZERO, Float2::new(1000.0, 1000.0), 100.0);
+        dirt.set_surface(
+            Float2::new(200.0, 200.0),
+            Float2::new(800.0, 600.0),
+            Surface::Rug,
+        );
+
+        assert_eq!(dirt.surface_at(Float2::new(250.0, 250.0)), Surface::Rug);
+        assert_eq!(dirt.surface_at(Float2::new(750.0, 550.0)), Surface::Rug);
+        assert_eq!(
+            dirt.surface_at(Float2::new(450.0, 650.0)),
+            Surface::Hardfloor
+        );
+        assert_eq!(
+            dirt.surface_at(Float2::new(50.0, 950.0)),
+            Surface::Hardfloor
+        );
+    }
+
+    #[test]
+    fn pickup_depends_on_surface_and_time() {
+        let picked = |surface: Surface, elapsed: Duration| {
+            let mut dirt = DirtMap::new(Float2::ZERO, Float2::new(1000.0, 1000.0), 100.0);
+            dirt.add_uniform(10.0);
+            dirt.set_surface(Float2::ZERO, Float2::new(1000.0, 1000.0), surface);
+            let total = dirt.total();
+            let picked = dirt.pickup(Float2::new(500.0, 500.0), 200.0, &elapsed);
+            assert!((dirt.total() - (total - picked)).abs() < 1e-4);
+            picked
+        };
+        let short = Duration::from_millis(100);
+
+        let hardfloor = picked(Surface::Hardfloor, short);
+        let carpet = picked(Surface::Carpet, short);
+        let rug = picked(Surface::Rug, short);
+        assert!(hardfloor > carpet && carpet > rug);
+        // 12 cells of 0.1 g under the robot, 1 - e^-0.3 of it on hardfloor
+        assert!((hardfloor - 1.2 * (1.0 - (-0.3f32).exp())).abs() < 1e-4);
+
+        // a slower pass picks up more, but never more than there is
+        assert!(picked(Surface::Hardfloor, short * 10) > hardfloor);
+        assert!((picked(Surface::Hardfloor, Duration::from_secs(100)) - 1.2).abs() < 1e-4);
+    }
+
+    #[test]
+    fn hotspots_peak_at_the_center() {
+        let mut dirt = DirtMap::new(Float2::ZERO, Float2::new(2000.0, 2000.0), 100.0);
+        dirt.add_hotspot(Float2::new(550.0, 550.0), 200.0, 40.0);
+
+        assert!((dirt.density_at(Float2::new(550.0, 550.0)) - 40.0).abs() < 1e-3);
+        // one sigma away
+        let sigma = 40.0 * (-0.5f32).exp();
+        assert!((dirt.density_at(Float2::new(750.0, 550.0)) - sigma).abs() < 1e-3);
+        // cut off beyond three sigma
+        assert_eq!(dirt.density_at(Float2::new(1250.0, 550.0)), 0.0);
+    }
+
+    #[test]
+    fn sensor_fires_above_the_threshold() {
+        let mut dirt = DirtMap::new(Float2::ZERO, Float2::new(2000.0, 2000.0), 100.0);
+        dirt.add_hotspot(Float2::new(550.0, 550.0), 200.0, 40.0);
+        let world = World::from_walls(Vec::new());
+        *world.dirt.lock().unwrap() = dirt;
+
+        let mut sensor = DirtSensor { threshold: 20.0 };
+        let mut fires = |x: f32| {
+            let pose = Pose::new(Float2::new(x, 550.0), Angle::ZERO);
+            sensor.update(&world, pose, Duration::ZERO)
+        };
+        assert_eq!(fires(550.0), Some(true));
+        // 24.3 and 13.0 g/m²
+        assert_eq!(fires(750.0), Some(true));
+        assert_eq!(fires(850.0), Some(false));
+        assert_eq!(fires(1500.0), Some(false));
+    }
+}
@@ -0,0 +1,111 @@
+use crate::units::AngularSpeed;
+
+use std::time::Duration;
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Wheel {
+    Left,
+    #[allow(dead_code)]
+    Right,
+}
+
+// LidarStopped = the head stops spinning, no more scans arrive
+// FrozenScan = the driver keeps publishing the last scan
+// BumperStuck = the bumper reports the value regardless of contact
+// WheelTorque = fraction of the commanded speed the wheel motor still delivers
+// GyroDrift = added to the gyro rate
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum FaultKind {
+    LidarStopped,
+    FrozenScan,
+    BumperStuck(bool),
+    WheelTorque(Wheel, f32),
+    GyroDrift(AngularSpeed),
+}
+
+// start = time since the robot started
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Fault {
+    pub kind: FaultKind,
+    pub start: Duration,
+    pub duration: Duration,
+}
+
+impl Fault {
+    pub fn new(kind: FaultKind, start: Duration, duration: Duration) -> Self {
+        Self {
+            kind,
+            start,
+            duration,
+        }
+    }
+
+    pub fn is_active(&self, time: Duration) -> bool {
+        time >= self.start && time < self.start + self.duration
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Scenario {
+    faults: Vec<Fault>,
+}
+
+impl Scenario {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn add(&mut self, fault: Fault) {
+        self.faults.push(fault);
+    }
+
+    pub fn active(&self, time: Duration) -> impl Iterator<Item = FaultKind> + '_ {
+        self.faults
+            .iter()
+            .filter(move |fault| fault.is_active(time))
+            .map(|fault| fault.kind)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn faults_are_active_in_their_window() {
+        let second = Duration::from_secs(1);
+        let fault = Fault::new(FaultKind::LidarStopped, second, 2 * second);
+        assert!(!fault.is_active(Duration::from_millis(999)));
+        assert!(fault.is_active(second));
+        assert!(fault.is_active(Duration::from_millis(2999)));
+        assert!(!fault.is_active(3 * second));
+    }
+
+    #[test]
+    fn scenarios_list_the_active_faults() {
+        let second = Duration::from_secs(1);
+        let mut scenario = Scenario::new();
+        scenario.add(Fault::new(FaultKind::FrozenScan, second, 2 * second));
+        scenario.add(Fault::new(
+            FaultKind::WheelTorque(Wheel::Left, 0.5),
+            2 * second,
+            2 * second,
+        ));
+
+        let active = |time| scenario.active(time).collect::<Vec<_>>();
+        assert_eq!(active(Duration::ZERO), Vec::new());
+        assert_eq!(active(second), vec![FaultKind::FrozenScan]);
+        assert_eq!(
+            active(2 * second),
+            vec![
+                FaultKind::FrozenScan,
+                FaultKind::WheelTorque(Wheel::Left, 0.5)
+            ]
+        );
+        assert_eq!(
+            active(3 * second),
+            vec![FaultKind::WheelTorque(Wheel::Left, 0.5)]
+        );
+        assert_eq!(active(4 * second), Vec::new());
+    }
+}
@@ -1,3 +1,5 @@
+use crate::angle::Angle;
+
 #[derive(Debug, Copy, Clone, PartialEq)]
 pub struct Float2(f32, f32);
 
@@ -49,6 +51,14 @@ impl std::ops::Div<f32> for Float2 {
     }
 }
 
+impl std::ops::Neg for Float2 {
+    type Output = Self;
+
+    fn neg(self) -> Self {
+        Self(-self.0, -self.1)
+    }
+}
+
 impl std::ops::AddAssign<Float2> for Float2 {
     fn add_assign(&mut self, rhs: Float2) {
         *self = Self(self.0 + rhs.0, self.1 + rhs.1)
@@ -61,8 +71,21 @@ impl std::ops::SubAssign<Float2> for Float2 {
     }
 }
 
-#[allow(dead_code)]
+impl From<(f32, f32)> for Float2 {
+    fn from((x, y): (f32, f32)) -> Self {
+        Self(x, y)
+    }
+}
+
+impl std::fmt::Display for Float2 {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "({}, {})", self.0, self.1)
+    }
+}
+
 impl Float2 {
+    pub const ZERO: Float2 = Float2(0.0, 0.0);
+
     pub fn new(a: f32, b: f32) -> Self {
         Float2(a, b)
     }
@@ -72,22 +95,73 @@ impl Float2 {
     pub fn get_y(&self) -> f32 {
         self.1
     }
-    pub fn set_x(&mut self, x: f32) {
-        self.0 = x;
-    }
-    pub fn set_y(&mut self, y: f32) {
-        self.1 = y;
-    }
     pub fn length(self) -> f32 {
         (self.0.powi(2) + self.1.powi(2)).sqrt()
     }
-    pub fn make_unit(mut self) -> Self {
-        let length = 1.0 / self.length();
-        self.0 *= length;
-        self.1 *= length;
-        self
+    // None for the zero vector instead of NaN
+    pub fn normalize(self) -> Option<Self> {
+        let length = self.length();
+        if length > 0.0 && length.is_finite() {
+            Some(self / length)
+        } else {
+            None
+        }
+    }
+    pub fn distance(self, other: Self) -> f32 {
+        (other - self).length()
+    }
+    pub fn dot(self, other: Self) -> f32 {
+        self.0 * other.0 + self.1 * other.1
+    }
+    // z component of the 3D cross product
+    // > 0 = other is counter clockwise of self
+    pub fn cross(self, other: Self) -> f32 {
+        self.0 * other.1 - self.1 * other.0
+    }
+    // rotated 90deg counter clockwise
+    pub fn perp(self) -> Self {
+        Self(-self.1, self.0)
+    }
+    pub fn rotate(self, angle: Angle) -> Self {
+        let (sin, cos) = angle.radians().sin_cos();
+        Self(self.0 * cos - self.1 * sin, self.0 * sin + self.1 * cos)
+    }
+    // signed angle from self to other
+    #[allow(dead_code)]
+    pub fn angle_between(self, other: Self) -> Angle {
+        Angle::from_radians(self.cross(other).atan2(self.dot(other)))
     }
-    pub fn print(&self) {
-        println!("x: {} y: {}", self.0, self.1);
+    // t = 0 -> self; t = 1 -> other
+    pub fn lerp(self, other: Self, t: f32) -> Self {
+        self + (other - self) * t
+    }
+    pub fn approx_eq(self, other: Self, epsilon: f32) -> bool {
+        (self.0 - other.0).abs() <= epsilon && (self.1 - other.1).abs() <= epsilon
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rotates_and_measures_angles() {
+        let x = Float2::new(2.0, 0.0);
+        let turned = x.rotate(Angle::from_degrees(90.0));
+        assert!(turned.approx_eq(Float2::new(0.0, 2.0), 1e-6));
+        assert!(turned.approx_eq(x.perp(), 1e-6));
+        assert!((x.angle_between(turned).degrees() - 90.0).abs() < 1e-4);
+        assert!((turned.angle_between(x).degrees() + 90.0).abs() < 1e-4);
+        assert_eq!(x.cross(turned), 4.0);
+    }
+
+    #[test]
+    fn normalizes_all_but_the_zero_vector() {
+        assert_eq!(
+            Float2::new(3.0, 4.0).normalize(),
+            Some(Float2::new(0.6, 0.8))
+        );
+        assert_eq!(Float2::ZERO.normalize(), None);
+        assert_eq!(Float2::new(f32::INFINITY, 0.0).normalize(), None);
     }
 }
@@ -0,0 +1,233 @@
+use crate::float2::Float2;
+use crate::pose::Pose;
+
+use std::collections::VecDeque;
+use std::time::Duration;
+
+// all frames: x = forward / right on the screen, y = left / up on the screen,
+// angles counter clockwise, lengths in mm
+//
+// world = fixed map frame
+// odom = where the robot believes it started, drifts against world
+// base_link = robot center, x = forward
+// sensor frames hang off base_link at their mount
+pub const WORLD: FrameId = FrameId(0);
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub struct FrameId(usize);
+
+// transforms = pose of the frame in its parent over time, oldest first
+// static frames keep a single transform that is valid at any time
+#[derive(Debug, Clone)]
+struct Frame {
+    name: String,
+    parent: Option<FrameId>,
+    transforms: VecDeque<(Duration, Pose)>,
+    fixed: bool,
+}
+
+#[derive(Debug, Clone)]
+pub struct FrameTree {
+    frames: Vec<Frame>,
+    history: Duration,
+}
+
+impl FrameTree {
+    // history = how long dynamic transforms are kept for lookups in the past
+    pub fn new(history: Duration) -> Self {
+        Self {
+            frames: vec![Frame {
+                name: "world".to_string(),
+                parent: None,
+                transforms: VecDeque::from([(Duration::ZERO, Pose::identity())]),
+                fixed: true,
+            }],
+            history,
+        }
+    }
+
+    // e.g. a sensor mount, never moves against its parent
+    pub fn add_static(&mut self, name: &str, parent: FrameId, pose: Pose) -> FrameId {
+        self.add(name, parent, VecDeque::from([(Duration::ZERO, pose)]), true)
+    }
+
+    // moves against its parent, see set_transform
+    pub fn add_dynamic(&mut self, name: &str, parent: FrameId) -> FrameId {
+        self.add(name, parent, VecDeque::new(), false)
+    }
+
+    fn add(
+        &mut self,
+        name: &str,
+        parent: FrameId,
+        transforms: VecDeque<(Duration, Pose)>,
+        fixed: bool,
+    ) -> FrameId {
+        self.frames.push(Frame {
+            name: name.to_string(),
+            parent: Some(parent),
+            transforms,
+            fixed,
+        });
+        FrameId(self.frames.len() - 1)
+    }
+
+    pub fn name(&self, id: FrameId) -> &str {
+        &self.frames[id.0].name
+    }
+
+    pub fn ids(&self) -> impl Iterator<Item = FrameId> {
+        (0..self.frames.len()).map(FrameId)
+    }
+
+    // stamps have to increase, a transform older than the last one is ignored
+    // and one with the same stamp replaces it
+    // older ones than the history get dropped
+    pub fn set_transform(&mut self, id: FrameId, time: Duration, pose: Pose) {
+        let history = self.history;
+        let frame = &mut self.frames[id.0];
+        if frame.fixed {
+            frame.transforms[0] = (Duration::ZERO, pose);
+            return;
+        }
+        match frame.transforms.back_mut() {
+            Some((stamp, _)) if *stamp > time => return,
+            Some((stamp, last)) if *stamp == time => *last = pose,
+            _ => frame.transforms.push_back((time, pose)),
+        }
+        while frame
+            .transforms
+            .front()
+            .is_some_and(|(stamp, _)| *stamp + history < time)
+        {
+            frame.transforms.pop_front();
+        }
+    }
+
+    // pose of the frame in its parent, interpolated between the stamps
+    // times outside the history use the closest transform
+    pub fn transform_at(&self, id: FrameId, time: Duration) -> Option<Pose> {
+        let frame = &self.frames[id.0];
+        if frame.fixed {
+            return frame.transforms.front().map(|(_, pose)| *pose);
+        }
+        let transforms = &frame.transforms;
+        let after = transforms.partition_point(|(stamp, _)| *stamp < time);
+        match (transforms.get(after.wrapping_sub(1)), transforms.get(after)) {
+            (Some((t0, p0)), Some((t1, p1))) => {
+                let t = (time - *t0).as_secs_f32() / (*t1 - *t0).as_secs_f32();
+                Some(p0.interpolate(*p1, t))
+            }
+            (None, Some((_, pose))) | (Some((_, pose)), None) => Some(*pose),
+            (None, None) => None,
+        }
+    }
+
+    // pose of the frame in world
+    pub fn world_pose(&self, id: FrameId, time: Duration) -> Option<Pose> {
+        let local = self.transform_at(id, time)?;
+        match self.frames[id.0].parent {
+            Some(parent) => Some(self.world_pose(parent, time)?.compose(local)),
+            None => Some(local),
+        }
+    }
+
+    // pose of source expressed in target, None if a transform is missing
+    pub fn lookup(&self, target: FrameId, source: FrameId, time: Duration) -> Option<Pose> {
+        let target = self.world_pose(target, time)?;
+        let source = self.world_pose(source, time)?;
+        Some(target.inverse().compose(source))
+    }
+
+    #[allow(dead_code)]
+    pub fn transform_point(
+        &self,
+        point: Float2,
+        from: FrameId,
+        to: FrameId,
+        time: Duration,
+    ) -> Option<Float2> {
+        Some(self.lookup(to, from, time)?.transform_point(point))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::angle::Angle;
+
+    fn at(x: f32, y: f32, degrees: f32) -> Pose {
+        Pose::new(Float2::new(x, y), Angle::from_degrees(degrees))
+    }
+
+    fn close(a: Pose, b: Pose) -> bool {
+        a.position.distance(b.position) < 1e-3
+            && a.heading.approx_eq(b.heading, Angle::from_degrees(1e-3))
+    }
+
+    #[test]
+    fn chains_through_static_and_dynamic_frames() {
+        let mut frames = FrameTree::new(Duration::from_secs(1));
+        let base = frames.add_dynamic("base_link", WORLD);
+        let sensor = frames.add_static("sensor", base, at(100.0, 0.0, 90.0));
+        frames.set_transform(base, Duration::ZERO, at(1000.0, 0.0, 90.0));
+
+        let pose = frames.world_pose(sensor, Duration::ZERO).unwrap();
+        assert!(close(pose, at(1000.0, 100.0, 180.0)));
+        let back = frames.lookup(sensor, WORLD, Duration::ZERO).unwrap();
+        assert!(close(pose.compose(back), Pose::identity()));
+
+        let ahead = frames
+            .transform_point(Float2::new(10.0, 0.0), sensor, WORLD, Duration::ZERO)
+            .unwrap();
+        assert!(ahead.approx_eq(Float2::new(990.0, 100.0), 1e-3));
+    }
+
+    #[test]
+    fn interpolates_between_stamps() {
+        let mut frames = FrameTree::new(Duration::from_secs(1));
+        let base = frames.add_dynamic("base_link", WORLD);
+        frames.set_transform(base, Duration::from_millis(100), at(0.0, 0.0, 0.0));
+        frames.set_transform(base, Duration::from_millis(200), at(100.0, 0.0, 20.0));
+
+        let pose = frames
+            .transform_at(base, Duration::from_millis(150))
+            .unwrap();
+        assert!(close(pose, at(50.0, 0.0, 10.0)));
+        // outside the stamps the closest one is used
+        let pose = frames
+            .transform_at(base, Duration::from_millis(500))
+            .unwrap();
+        assert!(close(pose, at(100.0, 0.0, 20.0)));
+    }
+
+    #[test]
+    fn ignores_stamps_going_backwards() {
+        let mut frames = FrameTree::new(Duration::from_secs(1));
+        let base = frames.add_dynamic("base_link", WORLD);
+        frames.set_transform(base, Duration::from_millis(200), at(100.0, 0.0, 0.0));
+        frames.set_transform(base, Duration::from_millis(100), at(-500.0, 0.0, 0.0));
+        frames.set_transform(base, Duration::from_millis(200), at(200.0, 0.0, 0.0));
+
+        for millis in [0, 150, 200, 300] {
+            let pose = frames
+                .transform_at(base, Duration::from_millis(millis))
+                .unwrap();
+            assert!(close(pose, at(200.0, 0.0, 0.0)));
+        }
+    }
+
+    #[test]
+    fn drops_transforms_older_than_the_history() {
+        let mut frames = FrameTree::new(Duration::from_millis(100));
+        let base = frames.add_dynamic("base_link", WORLD);
+        frames.set_transform(base, Duration::ZERO, at(0.0, 0.0, 0.0));
+        frames.set_transform(base, Duration::from_millis(200), at(200.0, 0.0, 0.0));
+        frames.set_transform(base, Duration::from_millis(250), at(250.0, 0.0, 0.0));
+
+        let pose = frames
+            .transform_at(base, Duration::from_millis(50))
+            .unwrap();
+        assert!(close(pose, at(200.0, 0.0, 0.0)));
+    }
+}
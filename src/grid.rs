@@ -0,0 +1,314 @@
+use crate::float2::Float2;
+use crate::line::{bounds, Line, EPSILON};
+
+// uniform grid over the walls, every cell lists the walls passing through it
+// walls reaching outside the grid are kept in a separate list and always tested
+// cell_size = mm
+// wall_count = highest inserted index + 1, sizes the bitset of tested walls in cast
+#[derive(Debug, Clone)]
+pub struct Grid {
+    origin: Float2,
+    cell_size: f32,
+    width: usize,
+    height: usize,
+    cells: Vec<Vec<usize>>,
+    outside: Vec<usize>,
+    wall_count: usize,
+}
+
+impl Grid {
+    // covers the bounding box of the walls
+    pub fn new(walls: &[Line], cell_size: f32) -> Self {
+        let (min, max) = bounds(walls);
+
+        let width = ((max.get_x() - min.get_x()) / cell_size).floor() as usize + 1;
+        let height = ((max.get_y() - min.get_y()) / cell_size).floor() as usize + 1;
+        let mut grid = Self {
+            origin: min,
+            cell_size,
+            width,
+            height,
+            cells: vec![Vec::new(); width * height],
+            outside: Vec::new(),
+            wall_count: 0,
+        };
+        for (index, wall) in walls.iter().enumerate() {
+            grid.insert(index, wall);
+        }
+        grid
+    }
+
+    // cell coordinates, may be outside the grid
+    fn cell(&self, point: Float2) -> (i64, i64) {
+        let local = (point - self.origin) / self.cell_size;
+        (local.get_x().floor() as i64, local.get_y().floor() as i64)
+    }
+
+    fn in_grid(&self, (x, y): (i64, i64)) -> bool {
+        x >= 0 && y >= 0 && (x as usize) < self.width && (y as usize) < self.height
+    }
+
+    fn cell_index(&self, (x, y): (i64, i64)) -> usize {
+        y as usize * self.width + x as usize
+    }
+
+    // cells the wall passes through, None if it leaves the grid
+    fn covered_cells(&self, wall: &Line) -> Option<Vec<usize>> {
+        let (ax, ay) = self.cell(wall.get_a());
+        let (bx, by) = self.cell(wall.get_b());
+        if !self.in_grid((ax, ay)) || !self.in_grid((bx, by)) {
+            return None;
+        }
+
+        // every cell of the bounding box whose circumcircle touches the wall,
+        // with some tolerance for walls passing exactly through a corner
+        let reach = self.cell_size * std::f32::consts::FRAC_1_SQRT_2 + EPSILON;
+        let mut cells = Vec::new();
+        for y in ay.min(by)..=ay.max(by) {
+            for x in ax.min(bx)..=ax.max(bx) {
+                let center =
+                    self.origin + Float2::new(x as f32 + 0.5, y as f32 + 0.5) * self.cell_size;
+                if wall.distance_to(center) <= reach {
+                    cells.push(self.cell_index((x, y)));
+                }
+            }
+        }
+        Some(cells)
+    }
+
+    pub fn insert(&mut self, index: usize, wall: &Line) {
+        self.wall_count = self.wall_count.max(index + 1);
+        match self.covered_cells(wall) {
+            Some(cells) => cells
+                .into_iter()
+                .for_each(|cell| self.cells[cell].push(index)),
+            None => self.outside.push(index),
+        }
+    }
+
+    pub fn remove(&mut self, index: usize, wall: &Line) {
+        match self.covered_cells(wall) {
+            Some(cells) => cells
+                .into_iter()
+                .for_each(|cell| self.cells[cell].retain(|other| *other != index)),
+            None => self.outside.retain(|other| *other != index),
+        }
+    }
+
+    // only the cells of the old and the new position get touched
+    pub fn update(&mut self, index: usize, old: &Line, new: &Line) {
+        self.remove(index, old);
+        self.insert(index, new);
+    }
+
+    // indices of the walls that may come closer than radius to the center
+    pub fn query_radius(&self, center: Float2, radius: f32) -> Vec<usize> {
+        let (min_x, min_y) = self.cell(center - Float2::new(radius, radius));
+        let (max_x, max_y) = self.cell(center + Float2::new(radius, radius));
+        let mut indices = self.outside.clone();
+        for y in min_y.max(0)..=max_y.min(self.height as i64 - 1) {
+            for x in min_x.max(0)..=max_x.min(self.width as i64 - 1) {
+                indices.extend(&self.cells[self.cell_index((x, y))]);
+            }
+        }
+        indices.sort_unstable();
+        indices.dedup();
+        indices
+    }
+
+    // walks the cells along the ray in order (Amanatides & Woo)
+    // test returns the distance at which the wall is hit
+    // stops as soon as a hit lies within the cells walked so far
+    // returns the closest hit, ties go to the lower index
+    pub fn cast<F>(
+        &self,
+        origin: Float2,
+        ray: Float2,
+        max_dist: f32,
+        mut test: F,
+    ) -> Option<(usize, f32)>
+    where
+        F: FnMut(usize) -> Option<f32>,
+    {
+        // a wall spans several cells but gets tested once
+        let mut tested = vec![0u64; self.wall_count.div_ceil(64)];
+        let mut closest: Option<(usize, f32)> = None;
+        let mut check = |index: usize, closest: &mut Option<(usize, f32)>| {
+            let (word, bit) = (index / 64, 1 << (index % 64));
+            if tested[word] & bit != 0 {
+                return;
+            }
+            tested[word] |= bit;
+            if let Some(distance) = test(index) {
+                let better = closest.is_none_or(|(best, best_distance)| {
+                    distance < best_distance || (distance == best_distance && index < best)
+                });
+                if better {
+                    *closest = Some((index, distance));
+                }
+            }
+        };
+        for index in &self.outside {
+            check(*index, &mut closest);
+        }
+
+        // move the start onto the grid if the ray begins outside of it
+        let size = Float2::new(self.width as f32, self.height as f32) * self.cell_size;
+        let (mut enter, mut exit) = (0.0f32, max_dist);
+        for (o, d, min, len) in [
+            (
+                origin.get_x(),
+                ray.get_x(),
+                self.origin.get_x(),
+                size.get_x(),
+            ),
+            (
+                origin.get_y(),
+                ray.get_y(),
+                self.origin.get_y(),
+                size.get_y(),
+            ),
+        ] {
+            if d == 0.0 {
+                if o < min || o > min + len {
+                    return closest;
+                }
+                continue;
+            }
+            let t0 = (min - o) / d;
+            let t1 = (min + len - o) / d;
+            enter = enter.max(t0.min(t1));
+            exit = exit.min(t0.max(t1));
+        }
+        if enter > exit {
+            return closest;
+        }
+
+        let start = origin + ray * enter;
+        let (mut x, mut y) = self.cell(start);
+        x = x.clamp(0, self.width as i64 - 1);
+        y = y.clamp(0, self.height as i64 - 1);
+
+        // distance along the ray to the next cell border and between borders
+        let axis = |o: f32, d: f32, cell: i64, min: f32| -> (i64, f32, f32) {
+            if d > 0.0 {
+                let border = min + (cell + 1) as f32 * self.cell_size;
+                (1, (border - o) / d, self.cell_size / d)
+            } else if d < 0.0 {
+                let border = min + cell as f32 * self.cell_size;
+                (-1, (border - o) / d, -self.cell_size / d)
+            } else {
+                (0, f32::INFINITY, f32::INFINITY)
+            }
+        };
+        let (step_x, mut next_x, delta_x) =
+            axis(origin.get_x(), ray.get_x(), x, self.origin.get_x());
+        let (step_y, mut next_y, delta_y) =
+            axis(origin.get_y(), ray.get_y(), y, self.origin.get_y());
+
+        while self.in_grid((x, y)) {
+            for index in &self.cells[self.cell_index((x, y))] {
+                check(*index, &mut closest);
+            }
+            let cell_exit = next_x.min(next_y);
+            if closest.is_some_and(|(_, distance)| distance <= cell_exit) || cell_exit > exit {
+                break;
+            }
+            if next_x < next_y {
+                x += step_x;
+                next_x += delta_x;
+            } else {
+                y += step_y;
+                next_y += delta_y;
+            }
+        }
+        closest
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn room() -> Vec<Line> {
+        vec![
+            Line::new(Float2::new(0.0, 0.0), Float2::new(5000.0, 0.0)),
+            Line::new(Float2::new(5000.0, 0.0), Float2::new(5000.0, 5000.0)),
+            Line::new(Float2::new(0.0, 5000.0), Float2::new(5000.0, 5000.0)),
+            Line::new(Float2::new(0.0, 0.0), Float2::new(0.0, 5000.0)),
+        ]
+    }
+
+    #[test]
+    fn finds_walls_through_cell_corners() {
+        // a post exactly on the corner of four cells
+        let mut walls = room();
+        walls.push(Line::new(
+            Float2::new(1000.0, 3000.0),
+            Float2::new(1000.0, 3000.0),
+        ));
+        let grid = Grid::new(&walls, 250.0);
+
+        assert!(grid
+            .query_radius(Float2::new(1010.0, 3010.0), 20.0)
+            .contains(&4));
+    }
+
+    #[test]
+    fn moved_walls_leave_their_old_cells() {
+        let mut walls = room();
+        let door = Line::new(Float2::new(1000.0, 1000.0), Float2::new(1000.0, 1800.0));
+        walls.push(door);
+        let mut grid = Grid::new(&walls, 250.0);
+        let opened = Line::new(Float2::new(1000.0, 1000.0), Float2::new(1800.0, 1000.0));
+        grid.update(4, &door, &opened);
+
+        assert!(!grid
+            .query_radius(Float2::new(1000.0, 1600.0), 10.0)
+            .contains(&4));
+        assert!(grid
+            .query_radius(Float2::new(1600.0, 1000.0), 10.0)
+            .contains(&4));
+        grid.remove(4, &opened);
+        assert!(!grid
+            .query_radius(Float2::new(1600.0, 1000.0), 10.0)
+            .contains(&4));
+    }
+
+    #[test]
+    fn cast_matches_testing_every_wall() {
+        let mut walls = room();
+        walls.push(Line::new(
+            Float2::new(1200.0, 800.0),
+            Float2::new(3100.0, 2900.0),
+        ));
+        walls.push(Line::new(
+            Float2::new(4000.0, 500.0),
+            Float2::new(4000.0, 4500.0),
+        ));
+        // reaches outside the grid
+        walls.push(Line::new(
+            Float2::new(2500.0, 2500.0),
+            Float2::new(9000.0, 2500.0),
+        ));
+        let grid = Grid::new(&walls[..6], 250.0);
+        let mut grid_all = grid.clone();
+        grid_all.insert(6, &walls[6]);
+
+        let distance = |origin: Float2, ray: Float2, index: usize| {
+            crate::utils::intersection_distance(origin, ray, walls[index])
+        };
+        for step in 0..360 {
+            let origin = Float2::new(700.0 + step as f32 * 7.0, 1900.0);
+            let ray = crate::angle::Angle::from_degrees(step as f32 * 1.3).to_vector();
+            let brute = (0..walls.len())
+                .filter_map(|index| distance(origin, ray, index).map(|d| (index, d)))
+                .filter(|(_, d)| *d <= 6000.0)
+                .min_by(|a, b| a.1.total_cmp(&b.1).then(a.0.cmp(&b.0)));
+            let cast = grid_all.cast(origin, ray, 6000.0, |index| {
+                distance(origin, ray, index).filter(|d| *d <= 6000.0)
+            });
+            assert_eq!(cast, brute);
+        }
+    }
+}
@@ -0,0 +1,39 @@
+use crate::angle::Angle;
+use crate::pose::Pose;
+use crate::sensor::{Mount, Sensor};
+use crate::units::AngularSpeed;
+use crate::world::World;
+
+use std::time::Duration;
+
+// yaw rate, bias = how far the gyro is off
+pub struct Gyro {
+    pub bias: AngularSpeed,
+    last: Option<(Angle, Duration)>,
+}
+
+impl Gyro {
+    pub fn new(bias: AngularSpeed) -> Self {
+        Self { bias, last: None }
+    }
+}
+
+impl Sensor for Gyro {
+    type Reading = AngularSpeed;
+
+    fn mount(&self) -> Mount {
+        Mount::center()
+    }
+
+    fn rate(&self) -> f32 {
+        f32::INFINITY
+    }
+
+    fn update(&mut self, _world: &World, pose: Pose, time: Duration) -> Option<AngularSpeed> {
+        let (last_heading, last_time) = self.last.replace((pose.heading, time))?;
+        if time == last_time {
+            return None;
+        }
+        Some((pose.heading - last_heading) / (time - last_time) + self.bias)
+    }
+}
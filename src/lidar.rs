@@ -0,0 +1,782 @@
+use crate::angle::Angle;
+use crate::float2::Float2;
+use crate::line::Line;
+use crate::material::Material;
+use crate::pose::Pose;
+use crate::raycast::{cast_batch, Ray};
+use crate::rng::Rng;
+use crate::sensor::{Mount, Sensor};
+use crate::units::Length;
+use crate::utils::intersection_distance;
+use crate::world::World;
+
+use std::time::Duration;
+
+// range_sigma = standard deviation as a fraction of the measured range
+// rates = probability per ray
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct LidarNoise {
+    pub range_sigma: f32,
+    pub dropout_rate: f32,
+    pub mixed_pixel_rate: f32,
+    pub edge_threshold: Length,
+    pub spurious_rate: f32,
+    pub spurious_max: Length,
+}
+
+impl Default for LidarNoise {
+    // roughly what a cheap triangulation lidar does
+    fn default() -> Self {
+        Self {
+            range_sigma: 0.01,
+            dropout_rate: 0.01,
+            mixed_pixel_rate: 0.5,
+            edge_threshold: Length::from_mm(300.0),
+            spurious_rate: 0.002,
+            spurious_max: Length::from_mm(500.0),
+        }
+    }
+}
+
+impl LidarNoise {
+    pub fn none() -> Self {
+        Self {
+            range_sigma: 0.0,
+            dropout_rate: 0.0,
+            mixed_pixel_rate: 0.0,
+            edge_threshold: Length::INFINITY,
+            spurious_rate: 0.0,
+            spurious_max: Length::ZERO,
+        }
+    }
+
+    // full_turn = the last ray neighbours the first one
+    pub fn apply(&self, scan: &mut Scan, max_dist: Length, full_turn: bool, rng: &mut Rng) {
+        let clean = scan.ranges.clone();
+
+        for (num, (range, intensity)) in scan
+            .ranges
+            .iter_mut()
+            .zip(scan.intensities.iter_mut())
+            .enumerate()
+        {
+            // mixed pixel: the beam hits the foreground and the background
+            // at a depth edge and returns something in between
+            let next = match clean.get(num + 1) {
+                Some(next) => *next,
+                None if full_turn => clean[0],
+                None => None,
+            };
+            if let (Some(value), Some(next)) = (range.as_mut(), next) {
+                if (next - *value).abs() > self.edge_threshold && rng.chance(self.mixed_pixel_rate)
+                {
+                    *value += (next - *value) * rng.next_f32();
+                }
+            }
+
+            if let Some(value) = range.as_mut() {
+                *value += *value * (rng.gaussian() * self.range_sigma);
+                *value = value.max(Length::ZERO).min(max_dist);
+            }
+
+            // dust or reflections close to the sensor
+            if rng.chance(self.spurious_rate) {
+                *range = Some(Length::from_mm(rng.range(0.0, self.spurious_max.mm())));
+                *intensity = rng.range(0.0, 0.1);
+            }
+
+            if rng.chance(self.dropout_rate) {
+                *range = None;
+                *intensity = 0.0;
+            }
+        }
+    }
+}
+
+// fov = degree, a plain f32 since an Angle wraps a full turn of 360.0 to 0.0
+// angle_offset = direction of the first ray, 0.0 = robot forward direction
+// scan_rate = Hz
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Lidar {
+    pub ray_count: usize,
+    pub fov: f32,
+    pub angle_offset: Angle,
+    pub scan_rate: f32,
+    pub max_dist: Length,
+    pub noise: LidarNoise,
+}
+
+impl Default for Lidar {
+    fn default() -> Self {
+        Self {
+            ray_count: 360,
+            fov: 360.0,
+            angle_offset: Angle::ZERO,
+            scan_rate: 10.0,
+            max_dist: Length::from_m(10.0),
+            noise: LidarNoise::none(),
+        }
+    }
+}
+
+impl Lidar {
+    pub fn full_turn(&self) -> bool {
+        self.fov >= 360.0
+    }
+
+    pub fn angle_increment(&self) -> f32 {
+        // a full circle doesn't repeat the first ray at the end
+        if self.full_turn() || self.ray_count < 2 {
+            self.fov / self.ray_count as f32
+        } else {
+            self.fov / (self.ray_count - 1) as f32
+        }
+    }
+
+    pub fn scan_period(&self) -> f32 {
+        1.0 / self.scan_rate
+    }
+
+    // fraction of the revolution at which the ray is captured
+    // rays outside of the fov are skipped while the head keeps spinning
+    pub fn ray_phase(&self, num: usize) -> f32 {
+        num as f32 * self.angle_increment() / 360.0
+    }
+
+    pub fn empty_scan(&self) -> Scan {
+        Scan {
+            angle_min: self.angle_offset.degrees(),
+            angle_increment: self.angle_increment(),
+            ranges: vec![None; self.ray_count],
+            intensities: vec![0.0; self.ray_count],
+            timestamps: vec![Duration::ZERO; self.ray_count],
+        }
+    }
+}
+
+const MAX_BOUNCES: usize = 4;
+// intensity that still reaches the max distance
+const REFERENCE_INTENSITY: f32 = 0.1;
+// keeps a traced ray from hitting the wall it just left
+const SURFACE_OFFSET: f32 = 0.01; // mm
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Hit {
+    pub distance: Length,
+    pub wall: Line,
+}
+
+impl Hit {
+    pub fn normal(&self) -> Float2 {
+        self.wall.normal().unwrap_or(Float2::ZERO)
+    }
+
+    // cosine of the angle between the ray and the wall normal
+    pub fn incidence(&self, ray: Float2) -> f32 {
+        ray.dot(self.normal()).abs()
+    }
+
+    // the returned intensity depends on the reflectivity of the wall
+    // and falls off with the angle of incidence
+    pub fn intensity(&self, ray: Float2) -> f32 {
+        self.wall.get_material().reflectivity() * self.incidence(ray)
+    }
+}
+
+// range = length of the whole beam path, mirrors put the return behind them
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Return {
+    pub range: Length,
+    pub intensity: f32,
+}
+
+// ray = unit vector in world coordinates
+// visible = whether the wall is seen at that distance, e.g. if it crosses the sensor plane
+// returns the closest visible wall within max_dist
+// only the walls in the grid cells along the ray are tested
+pub fn cast_ray<F>(
+    world: &World,
+    origin: Float2,
+    ray: Float2,
+    max_dist: f32,
+    visible: F,
+) -> Option<Hit>
+where
+    F: Fn(&Line, f32) -> bool,
+{
+    let geometry = world.geometry();
+    let walls = geometry.walls();
+    geometry
+        .get_grid()
+        .cast(origin, ray, max_dist, |index| {
+            intersection_distance(origin, ray, walls[index])
+                .filter(|distance| *distance <= max_dist && visible(&walls[index], *distance))
+        })
+        .map(|(index, distance)| Hit {
+            distance: Length::from_mm(distance),
+            wall: walls[index],
+        })
+}
+
+// same as cast_ray for a whole batch of rays, the world gets locked once
+// large batches are cast in parallel
+pub fn cast_rays<F>(world: &World, rays: &[Ray], visible: F) -> Vec<Option<Hit>>
+where
+    F: Fn(&Line, f32) -> bool + Sync,
+{
+    let geometry = world.geometry();
+    let walls = geometry.walls();
+    cast_batch(
+        walls,
+        geometry.get_arrays(),
+        geometry.get_grid(),
+        rays,
+        visible,
+    )
+    .into_iter()
+    .map(|hit| {
+        hit.map(|(index, distance)| Hit {
+            distance: Length::from_mm(distance),
+            wall: walls[index],
+        })
+    })
+    .collect()
+}
+
+// follows the beam through glass and off mirrors until it returns from a surface
+// dark surfaces return too little light to reach max_dist,
+// the angle of incidence only lowers the intensity, not the range
+// only walls crossing the scan plane at height are seen
+#[allow(dead_code)]
+pub fn trace_ray(
+    world: &World,
+    origin: Float2,
+    ray: Float2,
+    max_dist: Length,
+    height: Length,
+    rng: &mut Rng,
+) -> Option<Return> {
+    let hit = cast_ray(world, origin, ray, max_dist.mm(), |wall, _| {
+        wall.spans(height)
+    });
+    trace_from_hit(world, origin, ray, hit, max_dist, height, rng)
+}
+
+// continues trace_ray after the first wall got hit, e.g. by a batch cast
+pub fn trace_from_hit(
+    world: &World,
+    origin: Float2,
+    ray: Float2,
+    first_hit: Option<Hit>,
+    max_dist: Length,
+    height: Length,
+    rng: &mut Rng,
+) -> Option<Return> {
+    let mut origin = origin;
+    let mut ray = ray;
+    let mut travelled = 0.0;
+    let mut energy = 1.0;
+    let mut first_hit = Some(first_hit);
+
+    for _ in 0..MAX_BOUNCES {
+        let hit = match first_hit.take() {
+            Some(hit) => hit,
+            None => cast_ray(world, origin, ray, max_dist.mm() - travelled, |wall, _| {
+                wall.spans(height)
+            }),
+        }?;
+        travelled += hit.distance.mm();
+
+        let returns = match hit.wall.get_material() {
+            Material::Diffuse(_) => true,
+            Material::Glass(visibility) => rng.chance(visibility * hit.incidence(ray)),
+            Material::Mirror => false,
+        };
+        if returns {
+            let intensity = energy * hit.intensity(ray);
+            let brightness = energy * hit.wall.get_material().reflectivity();
+            let range = max_dist * (brightness / REFERENCE_INTENSITY).sqrt().min(1.0);
+            if travelled > range.mm() {
+                return None;
+            }
+            return Some(Return {
+                range: Length::from_mm(travelled),
+                intensity,
+            });
+        }
+
+        let point = origin + ray * hit.distance.mm();
+        let material = hit.wall.get_material();
+        match material {
+            Material::Mirror => {
+                let normal = hit.normal();
+                let dot = ray.dot(normal);
+                ray -= normal * (2.0 * dot);
+                energy *= material.reflectivity();
+            }
+            // the light not reflected by the glass passes through
+            _ => energy *= 1.0 - material.reflectivity(),
+        }
+        origin = point + ray * SURFACE_OFFSET;
+    }
+    None
+}
+
+// every ray is captured at its own time while the robot keeps moving
+// angles and ranges are relative to the robot pose at the timestamp of the ray
+// ranges = None if nothing got hit within the max distance
+// intensities = 0.0..1.0
+// timestamps = time since the robot started
+#[derive(Debug, Clone, PartialEq)]
+pub struct Scan {
+    pub angle_min: f32,
+    pub angle_increment: f32,
+    pub ranges: Vec<Option<Length>>,
+    pub intensities: Vec<f32>,
+    pub timestamps: Vec<Duration>,
+}
+
+impl Scan {
+    pub fn angle(&self, num: usize) -> Angle {
+        Angle::from_degrees(self.angle_min + num as f32 * self.angle_increment)
+    }
+
+    // index and range of the closest return
+    pub fn closest(&self) -> Option<(usize, Length)> {
+        self.ranges
+            .iter()
+            .enumerate()
+            .filter_map(|(num, range)| range.map(|range| (num, range)))
+            .min_by(|a, b| a.1.mm().total_cmp(&b.1.mm()))
+    }
+}
+
+// the lidar head spins once per scan period, independent of the control loop
+// every ray is cast from the pose at the time it gets captured,
+// interpolated between the last and the current update
+pub struct LidarSensor {
+    config: Lidar,
+    mount: Mount,
+    phase: f32, // fraction of a revolution
+    last_pose: Option<(Pose, Duration)>,
+    scan: Scan,
+    rng: Rng,
+}
+
+impl LidarSensor {
+    pub fn new(config: Lidar, mount: Mount, seed: u64) -> Self {
+        Self {
+            config,
+            mount,
+            phase: 0.0,
+            last_pose: None,
+            scan: config.empty_scan(),
+            rng: Rng::new(seed),
+        }
+    }
+
+    pub fn set_config(&mut self, config: Lidar) {
+        self.config = config;
+        self.phase = 0.0;
+        self.scan = config.empty_scan();
+    }
+}
+
+// publishes a scan once per revolution
+impl Sensor for LidarSensor {
+    type Reading = Scan;
+
+    fn mount(&self) -> Mount {
+        self.mount
+    }
+
+    fn rate(&self) -> f32 {
+        f32::INFINITY
+    }
+
+    fn update(&mut self, world: &World, pose: Pose, time: Duration) -> Option<Scan> {
+        let (last_pose, last_time) = self.last_pose.replace((pose, time))?;
+        let elapsed = time - last_time;
+        if elapsed.is_zero() {
+            return None;
+        }
+
+        let advance = (elapsed.as_secs_f32() / self.config.scan_period()).min(1.0);
+        let start_phase = self.phase;
+        let end_phase = start_phase + advance;
+        let mut revolutions = 0.0;
+        let mut published = None;
+        loop {
+            // the first hits of all rays are cast as one batch,
+            // the bounces follow one by one to keep the random numbers in order
+            let mut captured = Vec::new();
+            let mut rays = Vec::new();
+            for num in 0..self.config.ray_count {
+                let phase = self.config.ray_phase(num) + revolutions;
+                if phase < start_phase || phase >= end_phase {
+                    continue;
+                }
+
+                // fraction of this update at which the ray is captured
+                let t = (phase - start_phase) / advance;
+                let ray_pose = last_pose.interpolate(pose, t);
+                captured.push((num, t));
+                rays.push(Ray {
+                    origin: ray_pose.position,
+                    direction: (ray_pose.heading + self.scan.angle(num)).to_vector(),
+                    max_dist: self.config.max_dist.mm(),
+                });
+            }
+            let height = self.mount.height;
+            let hits = cast_rays(world, &rays, |wall, _| wall.spans(height));
+
+            for ((num, t), (ray, hit)) in captured.into_iter().zip(rays.iter().zip(hits)) {
+                let ret = trace_from_hit(
+                    world,
+                    ray.origin,
+                    ray.direction,
+                    hit,
+                    self.config.max_dist,
+                    height,
+                    &mut self.rng,
+                );
+                self.scan.ranges[num] = ret.map(|ret| ret.range);
+                self.scan.intensities[num] = ret.map_or(0.0, |ret| ret.intensity);
+                self.scan.timestamps[num] = time - elapsed.mul_f32(1.0 - t);
+            }
+
+            if end_phase - revolutions < 1.0 {
+                break;
+            }
+
+            // revolution finished
+            let mut scan = std::mem::replace(&mut self.scan, self.config.empty_scan());
+            self.config.noise.apply(
+                &mut scan,
+                self.config.max_dist,
+                self.config.full_turn(),
+                &mut self.rng,
+            );
+            published = Some(scan);
+            revolutions += 1.0;
+        }
+        self.phase = end_phase - revolutions;
+        published
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn trace(world: &World, ray: Float2) -> Option<Return> {
+        trace_ray(
+            world,
+            Float2::ZERO,
+            ray,
+            Length::from_mm(4000.0),
+            Length::from_mm(90.0),
+            &mut Rng::new(1),
+        )
+    }
+
+    // updates the lidar every 10 ms like the control loop
+    fn spin(
+        config: Lidar,
+        world: &World,
+        ticks: u64,
+        pose_at: impl Fn(Duration) -> Pose,
+    ) -> Vec<Scan> {
+        let mount = Mount::center().with_height(Length::from_mm(90.0));
+        let mut sensor = LidarSensor::new(config, mount, 1);
+        (0..=ticks)
+            .filter_map(|tick| {
+                let time = Duration::from_millis(tick * 10);
+                sensor.update(world, pose_at(time), time)
+            })
+            .collect()
+    }
+
+    fn standing(_: Duration) -> Pose {
+        Pose::new(Float2::ZERO, Angle::ZERO)
+    }
+
+    // a long wall 1 m to the left
+    fn side_wall() -> World {
+        World::from_walls(vec![Line::new(
+            Float2::new(-20000.0, 1000.0),
+            Float2::new(20000.0, 1000.0),
+        )])
+    }
+
+    #[test]
+    fn publishes_one_scan_per_period() {
+        let config = Lidar::default();
+        let scans = spin(config, &side_wall(), 105, standing);
+        assert_eq!(scans.len(), 10);
+
+        // every ray is captured a bit later than the one before, over one revolution
+        for scan in &scans {
+            assert!(scan.timestamps.windows(2).all(|pair| pair[0] < pair[1]));
+            let span = *scan.timestamps.last().unwrap() - scan.timestamps[0];
+            let expected = config.scan_period() * config.ray_phase(config.ray_count - 1);
+            assert!((span.as_secs_f32() - expected).abs() < 1e-3, "{span:?}");
+        }
+        for pair in scans.windows(2) {
+            let gap = pair[1].timestamps[0] - pair[0].timestamps[0];
+            assert!(
+                (gap.as_secs_f32() - config.scan_period()).abs() < 1e-3,
+                "{gap:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn rays_are_cast_from_the_pose_at_their_time() {
+        // driving towards the wall at 1 m/s, 100 mm per revolution
+        let moving =
+            |time: Duration| Pose::new(Float2::new(0.0, time.as_secs_f32() * 1000.0), Angle::ZERO);
+        let scans = spin(Lidar::default(), &side_wall(), 30, moving);
+        assert_eq!(scans.len(), 3);
+
+        for scan in &scans {
+            let mut checked = 0;
+            for (num, range) in scan.ranges.iter().enumerate() {
+                let ray = scan.angle(num).to_vector();
+                let position = moving(scan.timestamps[num]).position;
+                let expected = (1000.0 - position.get_y()) / ray.get_y();
+                if ray.get_y() > 0.2 {
+                    assert!((range.unwrap().mm() - expected).abs() < 0.5, "{num}");
+                    checked += 1;
+                }
+            }
+            assert!(checked > 100);
+        }
+        // the wall seems closer on the rays captured later
+        let scan = &scans[0];
+        assert!(scan.ranges[60].unwrap() > scan.ranges[120].unwrap());
+    }
+
+    #[test]
+    fn partial_scans_skip_the_back() {
+        // 270 degree in 720 rays, centered on the forward direction
+        let config = Lidar {
+            ray_count: 720,
+            fov: 270.0,
+            angle_offset: Angle::from_degrees(-135.0),
+            ..Lidar::default()
+        };
+        let scans = spin(config, &side_wall(), 105, standing);
+        assert_eq!(scans.len(), 10);
+
+        let scan = &scans[0];
+        assert_eq!(scan.ranges.len(), 720);
+        assert!(scan
+            .angle(0)
+            .approx_eq(Angle::from_degrees(-135.0), Angle::from_degrees(0.01)));
+        assert!(scan
+            .angle(719)
+            .approx_eq(Angle::from_degrees(135.0), Angle::from_degrees(0.01)));
+        // the head spends the last quarter of the revolution behind the robot
+        let span = *scan.timestamps.last().unwrap() - scan.timestamps[0];
+        assert!((span.as_secs_f32() - 0.075).abs() < 1e-3, "{span:?}");
+        // the wall on the left only
+        let left = 1000.0 / scan.angle(599).to_vector().get_y();
+        assert!((scan.ranges[599].unwrap().mm() - left).abs() < 0.5);
+        assert!(scan.ranges[120].is_none());
+    }
+
+    #[test]
+    fn walls_return_at_grazing_angles() {
+        // hit at about 85 degree incidence, close to the max distance
+        let world = World::from_walls(vec![Line::new(
+            Float2::new(-1000.0, 300.0),
+            Float2::new(5000.0, 300.0),
+        )]);
+        let ray = Angle::from_degrees(5.0).to_vector();
+        let ret = trace(&world, ray).unwrap();
+        assert!((ret.range.mm() - 300.0 / ray.get_y()).abs() < 0.1);
+        assert!(ret.intensity < REFERENCE_INTENSITY);
+    }
+
+    #[test]
+    fn dark_walls_have_a_shorter_range() {
+        let wall = |x: f32, material: Material| {
+            Line::with_material(Float2::new(x, -500.0), Float2::new(x, 500.0), material)
+        };
+        let ray = Float2::new(1.0, 0.0);
+        // 0.05 reflectivity reaches sqrt(0.5) of the max distance
+        assert!(trace(&World::from_walls(vec![wall(2500.0, Material::BLACK)]), ray).is_some());
+        assert!(trace(&World::from_walls(vec![wall(3000.0, Material::BLACK)]), ray).is_none());
+        assert!(trace(&World::from_walls(vec![wall(3900.0, Material::WALL)]), ray).is_some());
+    }
+
+    #[test]
+    fn mirrors_and_glass_return_from_behind() {
+        let wall = |x: f32, material: Material| {
+            Line::with_material(Float2::new(x, -500.0), Float2::new(x, 500.0), material)
+        };
+        let ray = Float2::new(1.0, 0.0);
+        // back off the mirror to the wall behind the sensor
+        let mirror = World::from_walls(vec![
+            wall(1000.0, Material::Mirror),
+            wall(-500.0, Material::WALL),
+        ]);
+        let ret = trace(&mirror, ray).unwrap();
+        assert!((ret.range.mm() - 2500.0).abs() < 0.1);
+        // a clear pane lets the ray through, a frosted one returns it
+        let behind = wall(2000.0, Material::WALL);
+        let clear = World::from_walls(vec![wall(1000.0, Material::Glass(0.0)), behind]);
+        assert!((trace(&clear, ray).unwrap().range.mm() - 2000.0).abs() < 0.1);
+        let frosted = World::from_walls(vec![wall(1000.0, Material::Glass(1.0)), behind]);
+        assert!((trace(&frosted, ray).unwrap().range.mm() - 1000.0).abs() < 0.1);
+    }
+
+    #[test]
+    fn batches_match_single_rays() {
+        let corner =
+            |a: (f32, f32), b: (f32, f32)| Line::new(Float2::new(a.0, a.1), Float2::new(b.0, b.1));
+        let mut walls = vec![
+            corner((-3000.0, -3000.0), (3000.0, -3000.0)),
+            corner((3000.0, -3000.0), (3000.0, 3000.0)),
+            corner((3000.0, 3000.0), (-3000.0, 3000.0)),
+            corner((-3000.0, 3000.0), (-3000.0, -3000.0)),
+            corner((-1200.0, 800.0), (1500.0, 2100.0)),
+            corner((500.0, -2500.0), (500.0, -400.0)),
+            corner((1000.0, 1000.0), (1000.0, 1000.0)),
+        ];
+        // enough rays for threads, single rays always walk the grid
+        let rays: Vec<Ray> = (0..1440)
+            .map(|num| Ray {
+                origin: Float2::new(num as f32 / 2.0 - 360.0, 100.0),
+                direction: Angle::from_degrees(num as f32 * 0.35).to_vector(),
+                max_dist: 4000.0,
+            })
+            .collect();
+        let visible = |wall: &Line, _| wall.spans(Length::from_mm(90.0));
+
+        let check = |walls: Vec<Line>| {
+            let world = World::from_walls(walls);
+            let batch = cast_rays(&world, &rays, visible);
+            for (ray, hit) in rays.iter().zip(batch) {
+                let single = cast_ray(&world, ray.origin, ray.direction, ray.max_dist, visible);
+                assert_eq!(
+                    hit.map(|hit| hit.distance.mm().to_bits()),
+                    single.map(|hit| hit.distance.mm().to_bits())
+                );
+                assert_eq!(hit, single);
+            }
+        };
+        // few walls get tested over the slices
+        check(walls.clone());
+        // table legs, then the batch walks the grid too
+        for leg in 0..40 {
+            let x = -2500.0 + (leg % 8) as f32 * 600.0;
+            let y = -2000.0 + (leg / 8) as f32 * 900.0;
+            walls.push(corner((x, y), (x + 40.0, y)));
+        }
+        check(walls);
+    }
+
+    fn noisy(noise: LidarNoise, ranges: &[f32], full_turn: bool) -> Scan {
+        let lidar = Lidar {
+            ray_count: ranges.len(),
+            fov: if full_turn { 360.0 } else { 270.0 },
+            noise,
+            ..Lidar::default()
+        };
+        let mut scan = lidar.empty_scan();
+        for (range, value) in scan.ranges.iter_mut().zip(ranges) {
+            *range = Some(Length::from_mm(*value));
+        }
+        scan.intensities.fill(0.5);
+        lidar.noise.apply(
+            &mut scan,
+            lidar.max_dist,
+            lidar.full_turn(),
+            &mut Rng::new(3),
+        );
+        scan
+    }
+
+    #[test]
+    fn noise_drops_rays() {
+        let noise = LidarNoise {
+            dropout_rate: 0.2,
+            ..LidarNoise::none()
+        };
+        let scan = noisy(noise, &[1000.0; 1000], true);
+
+        let dropped = scan.ranges.iter().filter(|range| range.is_none()).count();
+        assert!((150..250).contains(&dropped), "{dropped}");
+        for (range, intensity) in scan.ranges.iter().zip(&scan.intensities) {
+            assert_eq!(*intensity == 0.0, range.is_none());
+        }
+    }
+
+    #[test]
+    fn noise_grows_with_the_range() {
+        let noise = LidarNoise {
+            range_sigma: 0.01,
+            ..LidarNoise::none()
+        };
+        let sigma = |range: f32| {
+            let scan = noisy(noise, &vec![range; 2000], true);
+            let variance = scan
+                .ranges
+                .iter()
+                .map(|value| (value.unwrap().mm() - range).powi(2))
+                .sum::<f32>()
+                / 2000.0;
+            variance.sqrt()
+        };
+
+        let (near, far) = (sigma(500.0), sigma(5000.0));
+        assert!((near - 5.0).abs() < 0.5, "{near}");
+        assert!((far - 50.0).abs() < 5.0, "{far}");
+    }
+
+    #[test]
+    fn spurious_returns_stay_close() {
+        let noise = LidarNoise {
+            spurious_rate: 1.0,
+            spurious_max: Length::from_mm(300.0),
+            ..LidarNoise::none()
+        };
+        let scan = noisy(noise, &[4000.0; 200], true);
+
+        for (range, intensity) in scan.ranges.iter().zip(&scan.intensities) {
+            assert!(range.unwrap() < Length::from_mm(300.0));
+            assert!(*intensity < 0.1);
+        }
+    }
+
+    #[test]
+    fn edges_mix_with_the_next_ray() {
+        let noise = LidarNoise {
+            mixed_pixel_rate: 1.0,
+            edge_threshold: Length::from_mm(300.0),
+            ..LidarNoise::none()
+        };
+        // steps after the first, the fifth and the seventh ray, and back to the first ray
+        let ranges = [
+            3000.0, 1000.0, 1000.0, 1000.0, 1000.0, 3000.0, 3000.0, 1000.0,
+        ];
+        let mixed =
+            |scan: &Scan, num: usize| scan.ranges[num] != Some(Length::from_mm(ranges[num]));
+
+        let partial = noisy(noise, &ranges, false);
+        assert!(mixed(&partial, 0));
+        assert!(mixed(&partial, 4));
+        assert!(mixed(&partial, 6));
+        assert!(!mixed(&partial, 7));
+        let value = partial.ranges[4].unwrap().mm();
+        assert!((1000.0..=3000.0).contains(&value), "{value}");
+        for num in [1, 2, 3, 5] {
+            assert!(!mixed(&partial, num));
+        }
+
+        let full = noisy(noise, &ranges, true);
+        assert!(mixed(&full, 7));
+    }
+}
@@ -1,15 +1,70 @@
 use crate::float2::Float2;
+use crate::material::Material;
+use crate::units::Length;
 
+// mm, distances below this count as touching
+pub const EPSILON: f32 = 1e-3;
+
+// t = parameter on the first segment, u = parameter on the second one
+// 0.0 = point a, 1.0 = point b
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Intersection {
+    pub point: Float2,
+    pub t: f32,
+    pub u: f32,
+}
+
+// z_min, z_max = above the floor
 #[derive(Debug, Copy, Clone, PartialEq)]
 pub struct Line {
     a: Float2,
     b: Float2,
+    material: Material,
+    z_min: Length,
+    z_max: Length,
 }
 
-#[allow(dead_code)]
 impl Line {
     pub fn new(a: Float2, b: Float2) -> Self {
-        Self { a, b }
+        Self {
+            a,
+            b,
+            material: Material::WALL,
+            z_min: Length::ZERO,
+            z_max: Length::INFINITY,
+        }
+    }
+
+    #[allow(dead_code)]
+    pub fn with_material(a: Float2, b: Float2, material: Material) -> Self {
+        Self {
+            material,
+            ..Self::new(a, b)
+        }
+    }
+
+    // e.g. a cable lying on the floor or a sofa the robot fits under
+    #[allow(dead_code)]
+    pub fn with_height(a: Float2, b: Float2, z_min: Length, z_max: Length) -> Self {
+        Self {
+            z_min,
+            z_max,
+            ..Self::new(a, b)
+        }
+    }
+
+    // does the shape cross the plane at height z?
+    pub fn spans(&self, z: Length) -> bool {
+        (self.z_min..=self.z_max).contains(&z)
+    }
+
+    // does the shape reach into the height range?
+    pub fn overlaps(&self, z_min: Length, z_max: Length) -> bool {
+        self.z_min <= z_max && z_min <= self.z_max
+    }
+
+    pub fn get_material(&self) -> Material {
+        self.material
     }
 
     pub fn get_a(&self) -> Float2 {
@@ -20,29 +75,340 @@ impl Line {
         self.b
     }
 
-    pub fn print(&self) {
-        println!("a x: {} y: {}", self.a.get_x(), self.a.get_y());
-        println!("b x: {} y: {}", self.b.get_x(), self.b.get_y());
+    pub fn length(&self) -> f32 {
+        self.a.distance(self.b)
+    }
+
+    pub fn midpoint(&self) -> Float2 {
+        self.a.lerp(self.b, 0.5)
+    }
+
+    // unit vector from a to b, None for a degenerate line
+    pub fn direction(&self) -> Option<Float2> {
+        (self.b - self.a).normalize()
+    }
+
+    // unit vector on the left side when looking from a to b
+    pub fn normal(&self) -> Option<Float2> {
+        self.direction().map(Float2::perp)
+    }
+
+    pub fn point_at(&self, t: f32) -> Float2 {
+        self.a.lerp(self.b, t)
+    }
+
+    // parameter of the closest point, clamped to the segment
+    pub fn project(&self, point: Float2) -> f32 {
+        let line = self.b - self.a;
+        let length_squared = line.dot(line);
+        if length_squared <= EPSILON * EPSILON {
+            return 0.0;
+        }
+        ((point - self.a).dot(line) / length_squared).clamp(0.0, 1.0)
+    }
+
+    pub fn closest_point(&self, point: Float2) -> Float2 {
+        self.point_at(self.project(point))
+    }
+
+    pub fn distance_to(&self, point: Float2) -> f32 {
+        point.distance(self.closest_point(point))
+    }
+
+    pub fn contains(&self, point: Float2) -> bool {
+        self.distance_to(point) <= EPSILON
+    }
+
+    // parallel if the lines drift apart by at most EPSILON over the length of the longer one,
+    // so the tolerance stays in mm instead of comparing the raw determinant
+    pub fn is_parallel(&self, other: &Line) -> bool {
+        match (self.direction(), other.direction()) {
+            (Some(dir1), Some(dir2)) => {
+                let length = self.length().max(other.length());
+                dir1.cross(dir2).abs() * length <= EPSILON
+            }
+            _ => true,
+        }
+    }
+
+    pub fn is_collinear(&self, other: &Line) -> bool {
+        let Some(normal) = self.normal() else {
+            return other.contains(self.a);
+        };
+        self.is_parallel(other)
+            && (other.a - self.a).dot(normal).abs() <= EPSILON
+            && (other.b - self.a).dot(normal).abs() <= EPSILON
     }
 
-    pub fn get_col_point(&self, target: Line) -> Float2 {
-        let a1 = self.b.get_y() - self.a.get_y();
-        let b1 = self.a.get_x() - self.b.get_x();
-        let c1 = a1 * (self.a.get_x()) + b1 * (self.a.get_y());
+    // shared part of two collinear lines as parameters on self, min <= max
+    pub fn collinear_overlap(&self, other: &Line) -> Option<(f32, f32)> {
+        if !self.is_collinear(other) {
+            return None;
+        }
+        let line = self.b - self.a;
+        let length_squared = line.dot(line);
+        if length_squared <= EPSILON * EPSILON {
+            return other.contains(self.a).then_some((0.0, 0.0));
+        }
+        let t0 = (other.a - self.a).dot(line) / length_squared;
+        let t1 = (other.b - self.a).dot(line) / length_squared;
+        let min = t0.min(t1).max(0.0);
+        let max = t0.max(t1).min(1.0);
+        let tolerance = EPSILON / length_squared.sqrt();
+        (min <= max + tolerance).then_some((min, max.max(min)))
+    }
 
-        let a2 = target.b.get_y() - target.a.get_y();
-        let b2 = target.a.get_x() - target.b.get_x();
-        let c2 = a2 * (target.a.get_x()) + b2 * (target.a.get_y());
+    // crossing point of both segments, touching end points count
+    // collinear lines return the start of their overlap
+    pub fn intersect(&self, other: &Line) -> Option<Intersection> {
+        let dir1 = self.b - self.a;
+        let dir2 = other.b - other.a;
+
+        if self.is_parallel(other) {
+            let (t, _) = self.collinear_overlap(other)?;
+            let point = self.point_at(t);
+            return Some(Intersection {
+                point,
+                t,
+                u: other.project(point),
+            });
+        }
 
-        let determinant = a1 * b2 - a2 * b1;
+        let determinant = dir1.cross(dir2);
+        let to_other = other.a - self.a;
+        let t = to_other.cross(dir2) / determinant;
+        let u = to_other.cross(dir1) / determinant;
 
-        // no intersection found
-        if determinant == 0.0 {
-            Float2::new(f32::MAX, f32::MAX)
+        // tolerance in mm, converted into parameter space of each line
+        let within = |param: f32, length: f32| {
+            let tolerance = EPSILON / length;
+            (-tolerance..=1.0 + tolerance).contains(&param)
+        };
+        if within(t, dir1.length()) && within(u, dir2.length()) {
+            Some(Intersection {
+                point: self.point_at(t.clamp(0.0, 1.0)),
+                t: t.clamp(0.0, 1.0),
+                u: u.clamp(0.0, 1.0),
+            })
         } else {
-            let x = (b2 * c1 - b1 * c2) / determinant;
-            let y = (a1 * c2 - a2 * c1) / determinant;
-            Float2::new(x, y)
+            None
+        }
+    }
+
+    // fraction 0.0..=1.0 of the motion at which a moving circle first touches the line
+    // a circle already touching it only gets stopped when it moves further in,
+    // so it can still slide along or back away from the line
+    pub fn sweep_circle(&self, center: Float2, motion: Float2, radius: f32) -> Option<f32> {
+        let closest = self.closest_point(center);
+        if center.distance(closest) <= radius {
+            return ((center - closest).dot(motion) < 0.0).then_some(0.0);
+        }
+
+        // flat side, the normal points towards the circle
+        let face = self.normal().and_then(|normal| {
+            let side = (center - self.a).dot(normal);
+            let normal = if side < 0.0 { -normal } else { normal };
+            let approach = motion.dot(normal);
+            if approach >= 0.0 {
+                return None;
+            }
+            let t = (side.abs() - radius) / -approach;
+            let contact = center + motion * t - normal * radius;
+            let u =
+                (contact - self.a).dot(self.b - self.a) / (self.b - self.a).dot(self.b - self.a);
+            ((0.0..=1.0).contains(&t) && (0.0..=1.0).contains(&u)).then_some(t)
+        });
+
+        // round caps at the end points
+        let cap = |point: Float2| {
+            let a = motion.dot(motion);
+            let to_center = center - point;
+            let b = 2.0 * motion.dot(to_center);
+            let c = to_center.dot(to_center) - radius * radius;
+            let discriminant = b * b - 4.0 * a * c;
+            if a == 0.0 || discriminant < 0.0 {
+                return None;
+            }
+            let t = (-b - discriminant.sqrt()) / (2.0 * a);
+            (0.0..=1.0).contains(&t).then_some(t)
+        };
+
+        [face, cap(self.a), cap(self.b)]
+            .into_iter()
+            .flatten()
+            .min_by(f32::total_cmp)
+    }
+
+    // intersection of the infinite lines through both segments
+    pub fn get_col_point(&self, target: Line) -> Option<Float2> {
+        if self.is_parallel(&target) {
+            return None;
         }
+        let dir1 = self.b - self.a;
+        let dir2 = target.b - target.a;
+        let t = (target.a - self.a).cross(dir2) / dir1.cross(dir2);
+        Some(self.point_at(t))
+    }
+}
+
+// corners of the axis aligned box around the lines, both at zero without any
+pub fn bounds(lines: &[Line]) -> (Float2, Float2) {
+    if lines.is_empty() {
+        return (Float2::ZERO, Float2::ZERO);
+    }
+    let mut min = Float2::new(f32::MAX, f32::MAX);
+    let mut max = Float2::new(f32::MIN, f32::MIN);
+    for point in lines.iter().flat_map(|line| [line.a, line.b]) {
+        min = Float2::new(
+            min.get_x().min(point.get_x()),
+            min.get_y().min(point.get_y()),
+        );
+        max = Float2::new(
+            max.get_x().max(point.get_x()),
+            max.get_y().max(point.get_y()),
+        );
+    }
+    (min, max)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::angle::Angle;
+
+    fn line(ax: f32, ay: f32, bx: f32, by: f32) -> Line {
+        Line::new(Float2::new(ax, ay), Float2::new(bx, by))
+    }
+
+    #[test]
+    fn parallel_tolerance_is_in_mm() {
+        let long = line(0.0, 0.0, 10000.0, 0.0);
+        let tilted = |degrees: f32, length: f32| {
+            let end = Angle::from_degrees(degrees).to_vector() * length;
+            line(0.0, 100.0, end.get_x(), 100.0 + end.get_y())
+        };
+        // 1.7mm apart after 10m
+        assert!(!long.is_parallel(&tilted(0.01, 10000.0)));
+        assert!(long.is_parallel(&tilted(0.000_001, 10000.0)));
+        // short lines may turn further
+        let short = line(0.0, 0.0, 1.0, 0.0);
+        assert!(short.is_parallel(&tilted(0.03, 1.0)));
+        assert!(!short.is_parallel(&tilted(10.0, 1.0)));
+    }
+
+    #[test]
+    fn intersects_segments() {
+        let a = line(0.0, 0.0, 10.0, 10.0);
+        let b = line(0.0, 10.0, 10.0, 0.0);
+        let hit = a.intersect(&b).unwrap();
+        assert!(hit.point.approx_eq(Float2::new(5.0, 5.0), EPSILON));
+        assert!((hit.t - 0.5).abs() < 1e-6 && (hit.u - 0.5).abs() < 1e-6);
+
+        // touching end points count, a near miss doesn't
+        assert!(a.intersect(&line(10.0, 10.0, 20.0, 0.0)).is_some());
+        assert!(a.intersect(&line(10.1, 10.0, 20.0, 0.0)).is_none());
+        // parallel but apart
+        assert!(a.intersect(&line(1.0, 0.0, 11.0, 10.0)).is_none());
+    }
+
+    #[test]
+    fn collinear_overlap() {
+        let a = line(0.0, 0.0, 10.0, 0.0);
+        assert_eq!(
+            a.collinear_overlap(&line(5.0, 0.0, 20.0, 0.0)),
+            Some((0.5, 1.0))
+        );
+        assert_eq!(
+            a.collinear_overlap(&line(-5.0, 0.0, 2.0, 0.0)),
+            Some((0.0, 0.2))
+        );
+        assert_eq!(a.collinear_overlap(&line(11.0, 0.0, 20.0, 0.0)), None);
+        assert_eq!(a.collinear_overlap(&line(0.0, 1.0, 10.0, 1.0)), None);
+        let hit = a.intersect(&line(20.0, 0.0, 5.0, 0.0)).unwrap();
+        assert_eq!(hit.t, 0.5);
+    }
+
+    #[test]
+    fn closest_point_and_distance() {
+        let a = line(0.0, 0.0, 10.0, 0.0);
+        assert_eq!(
+            a.closest_point(Float2::new(3.0, 4.0)),
+            Float2::new(3.0, 0.0)
+        );
+        assert_eq!(a.distance_to(Float2::new(3.0, 4.0)), 4.0);
+        // beyond the end the end point is the closest
+        assert_eq!(a.distance_to(Float2::new(13.0, 4.0)), 5.0);
+        assert_eq!(a.length(), 10.0);
+        assert_eq!(a.midpoint(), Float2::new(5.0, 0.0));
+        assert_eq!(a.normal(), Some(Float2::new(0.0, 1.0)));
+        assert_eq!(line(1.0, 1.0, 1.0, 1.0).direction(), None);
+        assert!(a.contains(Float2::new(5.0, 0.0005)));
+    }
+
+    #[test]
+    fn circles_hit_the_face_and_the_caps() {
+        let wall = line(0.0, 0.0, 100.0, 0.0);
+        let center = Float2::new(50.0, 30.0);
+        // straight onto the face, touching after 20 of 40
+        let t = wall
+            .sweep_circle(center, Float2::new(0.0, -40.0), 10.0)
+            .unwrap();
+        assert!((t - 0.5).abs() < 1e-6);
+        // past the end, the round cap stops it where the distance to (100, 0) is 10
+        let t = wall
+            .sweep_circle(Float2::new(130.0, 5.0), Float2::new(-40.0, 0.0), 10.0)
+            .unwrap();
+        let touching = Float2::new(130.0 - 40.0 * t, 5.0);
+        assert!((touching.distance(wall.get_b()) - 10.0).abs() < 1e-3);
+        assert!(touching.get_x() > 100.0);
+        // too short or the wrong way
+        assert_eq!(
+            wall.sweep_circle(center, Float2::new(0.0, -19.0), 10.0),
+            None
+        );
+        assert_eq!(
+            wall.sweep_circle(center, Float2::new(0.0, 40.0), 10.0),
+            None
+        );
+    }
+
+    #[test]
+    fn grazing_circles_pass() {
+        let wall = line(0.0, 0.0, 100.0, 0.0);
+        // 0.01 beside the face and beside the caps
+        let along = Float2::new(200.0, 0.0);
+        assert_eq!(
+            wall.sweep_circle(Float2::new(-50.0, 10.01), along, 10.0),
+            None
+        );
+        let past = Float2::new(0.0, 200.0);
+        assert_eq!(
+            wall.sweep_circle(Float2::new(110.01, -100.0), past, 10.0),
+            None
+        );
+        assert!(wall
+            .sweep_circle(Float2::new(109.0, -100.0), past, 10.0)
+            .is_some());
+    }
+
+    #[test]
+    fn touching_circles_only_stop_moving_in() {
+        let wall = line(0.0, 0.0, 100.0, 0.0);
+        let center = Float2::new(50.0, 10.0);
+        assert_eq!(wall.sweep_circle(center, Float2::new(0.0, 5.0), 10.0), None);
+        assert_eq!(wall.sweep_circle(center, Float2::new(5.0, 0.0), 10.0), None);
+        assert_eq!(
+            wall.sweep_circle(center, Float2::new(5.0, -1.0), 10.0),
+            Some(0.0)
+        );
+    }
+
+    #[test]
+    fn long_steps_dont_tunnel_through_thin_walls() {
+        let wall = line(0.0, -100.0, 0.0, 100.0);
+        let t = wall
+            .sweep_circle(Float2::new(-500.0, 0.0), Float2::new(1000.0, 0.0), 10.0)
+            .unwrap();
+        assert!((t - 0.49).abs() < 1e-6);
     }
 }
@@ -1,20 +1,51 @@
+mod angle;
+mod bumper;
+mod depth_camera;
+mod dirt;
+mod fault;
 mod float2;
+mod frames;
+mod grid;
+mod gyro;
+mod lidar;
 mod line;
+mod material;
+mod polygon;
+mod pose;
+mod rangefinder;
+mod raycast;
+mod rng;
 mod robot;
+mod sdf;
+mod sensor;
+mod units;
 mod utils;
 mod window;
+mod world;
 
+use std::f32::consts::TAU;
 use std::sync::atomic::AtomicBool;
 use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
 
 use window::Viewport;
 
+use crate::angle::Angle;
+use crate::dirt::{DirtMap, Surface};
+use crate::fault::{Fault, FaultKind, Scenario, Wheel};
 use crate::float2::Float2;
+use crate::lidar::{Lidar, LidarNoise};
 use crate::line::Line;
-use crate::robot::Robot;
+use crate::robot::{Robot, SensorSuite};
+use crate::units::{AngularSpeed, Length};
+use crate::utils::run_with_interval;
+use crate::world::World;
+
+const DOOR_PERIOD: f32 = 8.0; // s for the door to open and close
 
 // X goes to the right
-// Y goes down
+// Y goes up, see frames.rs
 
 // TODO: update to use new Arc<Mutex<>> room
 
@@ -58,19 +89,97 @@ fn main() {
         Line::new(Float2::new(0.0, 5000.0), Float2::new(5000.0, 5000.0)),
         Line::new(Float2::new(0.0, 0.0), Float2::new(0.0, 5000.0)),
     ]);
-    let room2 = Arc::new(vec![
+    let room2 = vec![
         Line::new(Float2::new(2500.0, 5000.0), Float2::new(2500.0, 2500.0)),
         Line::new(Float2::new(2500.0, 2500.0), Float2::new(0.0, 2500.0)),
-    ]);
+    ];
+
+    // light dust everywhere, crumbs in front of the kitchen counter
+    let mut dirt = DirtMap::new(Float2::new(0.0, 0.0), Float2::new(5000.0, 5000.0), 50.0);
+    dirt.add_uniform(0.5);
+    dirt.add_hotspot(Float2::new(4000.0, 4600.0), 250.0, 20.0);
+    dirt.set_surface(
+        Float2::new(500.0, 500.0),
+        Float2::new(2000.0, 2000.0),
+        Surface::Carpet,
+    );
+    dirt.set_surface(
+        Float2::new(3000.0, 500.0),
+        Float2::new(4500.0, 1500.0),
+        Surface::Rug,
+    );
+    let initial_dirt = dirt.total();
+    let world = Arc::new(World::new(room2, dirt));
 
     let quit = Arc::new(AtomicBool::new(false));
 
-    let ilse = Robot::new(3000.0, 4700.0);
+    // --door swings a door on the corner of the walls while the robot drives
+    let door_thread = std::env::args().any(|arg| arg == "--door").then(|| {
+        let hinge = Float2::new(2500.0, 2500.0);
+        let door = move |angle: Angle| Line::new(hinge, hinge + angle.to_vector() * 900.0);
+        let index = world.add_wall(door(Angle::ZERO));
+        let (world, quit) = (Arc::clone(&world), Arc::clone(&quit));
+        thread::spawn(move || {
+            let start = Instant::now();
+            run_with_interval(Duration::from_millis(20), &quit, |_| {
+                let swing = (start.elapsed().as_secs_f32() * TAU / DOOR_PERIOD).sin();
+                world.set_wall(index, door(Angle::from_degrees(-45.0 + 45.0 * swing)));
+                false
+            });
+        })
+    });
+
+    // --faults replays a scenario of sensor and motor faults, off by default
+    let mut scenario = Scenario::new();
+    if std::env::args().any(|arg| arg == "--faults") {
+        let secs = Duration::from_secs;
+        scenario.add(Fault::new(
+            FaultKind::GyroDrift(AngularSpeed::from_degrees_per_s(2.0)),
+            secs(20),
+            secs(15),
+        ));
+        scenario.add(Fault::new(
+            FaultKind::WheelTorque(Wheel::Left, 0.5),
+            secs(40),
+            secs(10),
+        ));
+        scenario.add(Fault::new(FaultKind::LidarStopped, secs(60), secs(10)));
+        scenario.add(Fault::new(FaultKind::FrozenScan, secs(80), secs(5)));
+        scenario.add(Fault::new(FaultKind::BumperStuck(true), secs(90), secs(3)));
+    }
+
+    // --depth-camera adds the forward depth camera to the lidar
+    let suite = if std::env::args().any(|arg| arg == "--depth-camera") {
+        SensorSuite::LidarAndDepthCamera
+    } else {
+        SensorSuite::Lidar
+    };
+
+    let mut ilse = Robot::new(Length::from_mm(3000.0), Length::from_mm(4700.0), suite);
+    ilse.set_scenario(scenario);
+    ilse.set_lidar(Lidar {
+        noise: LidarNoise::default(),
+        ..Lidar::default()
+    });
     let ilse_state = ilse.get_state();
-    let robot_thread = ilse.run(Arc::clone(&room2), Arc::clone(&quit));
+    let robot_thread = ilse.run(Arc::clone(&world), Arc::clone(&quit));
 
     let mut viewport = Viewport::new();
-    viewport.draw(Arc::clone(&room2), &ilse_state, Arc::clone(&quit));
+    viewport.draw(Arc::clone(&world), &ilse_state, Arc::clone(&quit));
 
     robot_thread.join().unwrap();
+    if let Some(door_thread) = door_thread {
+        door_thread.join().unwrap();
+    }
+
+    let residual_dirt = world.dirt.lock().unwrap().total();
+    println!(
+        "residual dirt: {:.2}g of {:.2}g ({:.1}%)",
+        residual_dirt,
+        initial_dirt,
+        residual_dirt / initial_dirt * 100.0
+    );
+    if let Some(error) = ilse_state.load().wall_error {
+        println!("wall following error: {:.1}mm rms", error.mm());
+    }
 }
@@ -0,0 +1,27 @@
+// how a wall looks to the lidar, every material is solid for collisions
+// Diffuse = reflectivity 0.0..1.0
+// Glass = fraction of the rays returning from the pane, the rest passes through
+// Mirror = reflects the rays and produces phantom returns behind it
+#[allow(dead_code)]
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Material {
+    Diffuse(f32),
+    Glass(f32),
+    Mirror,
+}
+
+impl Material {
+    pub const WALL: Material = Material::Diffuse(0.5);
+    #[allow(dead_code)]
+    pub const BLACK: Material = Material::Diffuse(0.05);
+    #[allow(dead_code)]
+    pub const GLASS: Material = Material::Glass(0.1);
+
+    pub fn reflectivity(&self) -> f32 {
+        match self {
+            Material::Diffuse(reflectivity) => *reflectivity,
+            Material::Glass(_) => 0.05,
+            Material::Mirror => 0.9,
+        }
+    }
+}
@@ -0,0 +1,627 @@
+use crate::float2::Float2;
+use crate::line::{Line, EPSILON};
+
+// a miter longer than this times the offset distance gets beveled
+const MITER_LIMIT: f32 = 2.0;
+// mm, how far beside an edge the winding number is checked
+const PROBE_DISTANCE: f32 = 10.0 * EPSILON;
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Winding {
+    Clockwise,
+    CounterClockwise,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Location {
+    Inside,
+    Outside,
+    Boundary,
+}
+
+// closed ring of points, the last point connects back to the first
+#[derive(Debug, Clone, PartialEq)]
+pub struct Polygon {
+    points: Vec<Float2>,
+}
+
+impl Polygon {
+    pub fn new(points: Vec<Float2>) -> Self {
+        Self { points }
+    }
+
+    // min and max are the corners of an axis aligned rectangle
+    #[allow(dead_code)]
+    pub fn rectangle(min: Float2, max: Float2) -> Self {
+        Self::new(vec![
+            min,
+            Float2::new(max.get_x(), min.get_y()),
+            max,
+            Float2::new(min.get_x(), max.get_y()),
+        ])
+    }
+
+    pub fn get_points(&self) -> &[Float2] {
+        &self.points
+    }
+
+    pub fn edges(&self) -> impl Iterator<Item = Line> + '_ {
+        let next = self.points.iter().cycle().skip(1);
+        self.points.iter().zip(next).map(|(a, b)| Line::new(*a, *b))
+    }
+
+    // > 0 = counter clockwise
+    pub fn signed_area(&self) -> f32 {
+        self.edges()
+            .map(|edge| edge.get_a().cross(edge.get_b()))
+            .sum::<f32>()
+            / 2.0
+    }
+
+    pub fn area(&self) -> f32 {
+        self.signed_area().abs()
+    }
+
+    pub fn winding(&self) -> Winding {
+        if self.signed_area() >= 0.0 {
+            Winding::CounterClockwise
+        } else {
+            Winding::Clockwise
+        }
+    }
+
+    pub fn reversed(&self) -> Self {
+        Self::new(self.points.iter().rev().copied().collect())
+    }
+
+    pub fn with_winding(&self, winding: Winding) -> Self {
+        if self.winding() == winding {
+            self.clone()
+        } else {
+            self.reversed()
+        }
+    }
+
+    // None for polygons without area
+    #[allow(dead_code)]
+    pub fn centroid(&self) -> Option<Float2> {
+        let area = self.signed_area();
+        if area.abs() <= EPSILON {
+            return None;
+        }
+        let sum = self.edges().fold(Float2::ZERO, |sum, edge| {
+            sum + (edge.get_a() + edge.get_b()) * edge.get_a().cross(edge.get_b())
+        });
+        Some(sum / (6.0 * area))
+    }
+
+    // even odd rule, points on an edge are on the boundary
+    pub fn locate(&self, point: Float2) -> Location {
+        if self.edges().any(|edge| edge.contains(point)) {
+            return Location::Boundary;
+        }
+        let crossings = self
+            .edges()
+            .filter(|edge| {
+                let (a, b) = (edge.get_a(), edge.get_b());
+                if (a.get_y() > point.get_y()) == (b.get_y() > point.get_y()) {
+                    return false;
+                }
+                let t = (point.get_y() - a.get_y()) / (b.get_y() - a.get_y());
+                a.get_x() + t * (b.get_x() - a.get_x()) > point.get_x()
+            })
+            .count();
+        if crossings % 2 == 1 {
+            Location::Inside
+        } else {
+            Location::Outside
+        }
+    }
+
+    pub fn contains(&self, point: Float2) -> bool {
+        self.locate(point) != Location::Outside
+    }
+
+    // fraction 0.0..=1.0 of the motion at which the moving polygon first touches the line
+    // either a corner runs into the line or an end of the line runs into an edge
+    // a polygon already touching the line only gets stopped when it moves further onto it,
+    // like Line::sweep_circle, so a convex footprint can slide along or back away
+    pub fn sweep(&self, motion: Float2, line: &Line) -> Option<f32> {
+        let touching = self.contains(line.get_a())
+            || self.contains(line.get_b())
+            || self.edges().any(|edge| edge.intersect(line).is_some());
+        if touching {
+            return self.moves_onto(motion, line).then_some(0.0);
+        }
+        let corners = self
+            .points
+            .iter()
+            .filter_map(|point| Line::new(*point, *point + motion).intersect(line));
+        let ends = [line.get_a(), line.get_b()].into_iter().flat_map(|point| {
+            let path = Line::new(point, point - motion);
+            self.edges().filter_map(move |edge| path.intersect(&edge))
+        });
+        corners
+            .chain(ends)
+            .map(|intersection| intersection.t)
+            .min_by(f32::total_cmp)
+    }
+
+    // for a polygon touching the line, does the motion push it further onto the line?
+    // lying on one side of it, moving towards that side does
+    // otherwise the line pokes at it with an end, moving against the edges there does
+    // anything crossing the inside already overlaps and stays stuck
+    fn moves_onto(&self, motion: Float2, line: &Line) -> bool {
+        if let Some(normal) = line.normal() {
+            let sides = self
+                .points
+                .iter()
+                .map(|point| (*point - line.get_a()).dot(normal));
+            let (min, max) = sides.fold((0.0f32, 0.0f32), |(min, max), side| {
+                (min.min(side), max.max(side))
+            });
+            if min >= -EPSILON {
+                return motion.dot(normal) < 0.0;
+            }
+            if max <= EPSILON {
+                return motion.dot(normal) > 0.0;
+            }
+        }
+
+        let polygon = self.with_winding(Winding::CounterClockwise);
+        let ends = [line.get_a(), line.get_b()];
+        let crosses = polygon.edges().any(|edge| {
+            edge.intersect(line).is_some_and(|intersection| {
+                ends.iter()
+                    .all(|end| end.distance(intersection.point) > EPSILON)
+            })
+        });
+        if crosses {
+            return true;
+        }
+        ends.into_iter().any(|end| match polygon.locate(end) {
+            Location::Inside => true,
+            // the outward normals of the edges at the end, two at a corner
+            Location::Boundary => polygon
+                .edges()
+                .filter(|edge| edge.contains(end))
+                .filter_map(|edge| edge.normal())
+                .all(|normal| motion.dot(-normal) > 0.0),
+            Location::Outside => false,
+        })
+    }
+
+    // every corner turns the same way, collinear points are allowed
+    #[allow(dead_code)]
+    pub fn is_convex(&self) -> bool {
+        let count = self.points.len();
+        if count < 3 {
+            return false;
+        }
+        let mut sign = 0.0;
+        for i in 0..count {
+            let a = self.points[i];
+            let b = self.points[(i + 1) % count];
+            let c = self.points[(i + 2) % count];
+            let turn = (b - a).cross(c - b);
+            if turn.abs() <= EPSILON {
+                continue;
+            }
+            if sign != 0.0 && turn.signum() != sign {
+                return false;
+            }
+            sign = turn.signum();
+        }
+        true
+    }
+
+    // + = outwards, - = inwards
+    // inflating the walls of a room by the robot radius gives the configuration space
+    // sharp corners are beveled instead of growing long spikes
+    // loops the shifted edges form at small features are cut away,
+    // so the result can be several regions, or none if everything shrinks away
+    #[allow(dead_code)]
+    pub fn offset(&self, distance: f32) -> Option<Vec<Region>> {
+        let polygon = self.with_winding(Winding::CounterClockwise);
+        let edges: Vec<Line> = polygon
+            .edges()
+            .filter(|edge| edge.length() > EPSILON)
+            .collect();
+        let count = edges.len();
+        let mut points = Vec::with_capacity(count);
+        for i in 0..count {
+            let prev = edges[(i + count - 1) % count];
+            let next = edges[i];
+            // the normal points to the left, which is inside for counter clockwise
+            let shift = |edge: Line| {
+                let normal = -edge.normal().unwrap_or(Float2::ZERO) * distance;
+                Line::new(edge.get_a() + normal, edge.get_b() + normal)
+            };
+            let (prev, next) = (shift(prev), shift(next));
+            match prev.get_col_point(next) {
+                Some(point) if point.distance(next.get_a()) <= MITER_LIMIT * distance.abs() => {
+                    points.push(point)
+                }
+                Some(_) => {
+                    points.push(prev.get_b());
+                    points.push(next.get_a());
+                }
+                None => points.push(next.get_a()),
+            }
+        }
+        filled(Self::new(points).edges().collect())
+    }
+
+    #[allow(dead_code)]
+    pub fn union(&self, other: &Polygon) -> Option<Vec<Region>> {
+        let a = self.with_winding(Winding::CounterClockwise);
+        let b = other.with_winding(Winding::CounterClockwise);
+        filled(a.edges().chain(b.edges()).collect())
+    }
+
+    // b winds the other way, so it takes away from a where they overlap
+    #[allow(dead_code)]
+    pub fn difference(&self, other: &Polygon) -> Option<Vec<Region>> {
+        let a = self.with_winding(Winding::CounterClockwise);
+        let b = other.with_winding(Winding::Clockwise);
+        filled(a.edges().chain(b.edges()).collect())
+    }
+
+    // how often the ring winds around the point, + = counter clockwise
+    #[allow(dead_code)]
+    pub fn winding_number(&self, point: Float2) -> i32 {
+        winding_number(self.edges(), point)
+    }
+
+    // a point just inside the ring, next to the middle of its longest edge
+    fn inner_point(&self) -> Option<Float2> {
+        let edge = self
+            .edges()
+            .max_by(|a, b| a.length().total_cmp(&b.length()))?;
+        let normal = edge.normal()?;
+        let inside = match self.winding() {
+            Winding::CounterClockwise => normal,
+            Winding::Clockwise => -normal,
+        };
+        Some(edge.midpoint() + inside * PROBE_DISTANCE)
+    }
+}
+
+// outline winds counter clockwise, the holes clockwise
+// results of the boolean operations and offsets, a hole in a hole is a separate region
+#[derive(Debug, Clone, PartialEq)]
+pub struct Region {
+    pub outline: Polygon,
+    pub holes: Vec<Polygon>,
+}
+
+impl Region {
+    #[allow(dead_code)]
+    pub fn area(&self) -> f32 {
+        self.outline.area() - self.holes.iter().map(Polygon::area).sum::<f32>()
+    }
+
+    // the boundaries of the holes belong to the region
+    #[allow(dead_code)]
+    pub fn locate(&self, point: Float2) -> Location {
+        match self.outline.locate(point) {
+            Location::Inside => self
+                .holes
+                .iter()
+                .map(|hole| hole.locate(point))
+                .find(|location| *location != Location::Outside)
+                .map_or(Location::Inside, |location| match location {
+                    Location::Inside => Location::Outside,
+                    _ => Location::Boundary,
+                }),
+            location => location,
+        }
+    }
+
+    #[allow(dead_code)]
+    pub fn contains(&self, point: Float2) -> bool {
+        self.locate(point) != Location::Outside
+    }
+}
+
+// boundary of the area the directed edges wind around at least once
+// the edges get cut where they cross and every piece is kept
+// if it has that area on one side and not on the other
+// None if the kept pieces don't close into rings
+fn filled(edges: Vec<Line>) -> Option<Vec<Region>> {
+    let mut kept: Vec<Line> = Vec::new();
+    for segment in split_edges(&edges) {
+        let Some(normal) = segment.normal() else {
+            continue;
+        };
+        let middle = segment.midpoint();
+        let left = winding_number(edges.iter().copied(), middle + normal * PROBE_DISTANCE) >= 1;
+        let right = winding_number(edges.iter().copied(), middle - normal * PROBE_DISTANCE) >= 1;
+        // the filled side always ends up on the left
+        let segment = match (left, right) {
+            (true, false) => segment,
+            (false, true) => Line::new(segment.get_b(), segment.get_a()),
+            _ => continue,
+        };
+        // e.g. an edge both polygons share in the same direction
+        let duplicate = kept.iter().any(|other| {
+            other.get_a().approx_eq(segment.get_a(), EPSILON)
+                && other.get_b().approx_eq(segment.get_b(), EPSILON)
+        });
+        if !duplicate {
+            kept.push(segment);
+        }
+    }
+    Some(regions(chain(kept)?))
+}
+
+// edges cut at every point where they cross or touch another edge
+fn split_edges(edges: &[Line]) -> Vec<Line> {
+    let mut segments = Vec::new();
+    for (index, edge) in edges.iter().enumerate() {
+        let mut params = vec![0.0, 1.0];
+        for (other, cut) in edges.iter().enumerate() {
+            if other == index {
+                continue;
+            }
+            match edge.collinear_overlap(cut) {
+                Some((t0, t1)) => params.extend([t0, t1]),
+                None => params.extend(edge.intersect(cut).map(|hit| hit.t)),
+            }
+        }
+        params.sort_by(|a, b| a.total_cmp(b));
+        params.dedup_by(|a, b| edge.point_at(*a).distance(edge.point_at(*b)) <= EPSILON);
+        segments.extend(
+            params
+                .windows(2)
+                .map(|t| Line::new(edge.point_at(t[0]), edge.point_at(t[1]))),
+        );
+    }
+    segments
+}
+
+// + for every counter clockwise turn around the point, - for every clockwise one
+fn winding_number(edges: impl Iterator<Item = Line>, point: Float2) -> i32 {
+    edges
+        .map(|edge| {
+            let (a, b) = (edge.get_a(), edge.get_b());
+            let side = (b - a).cross(point - a);
+            if a.get_y() <= point.get_y() && b.get_y() > point.get_y() && side > 0.0 {
+                1
+            } else if a.get_y() > point.get_y() && b.get_y() <= point.get_y() && side < 0.0 {
+                -1
+            } else {
+                0
+            }
+        })
+        .sum()
+}
+
+// every hole goes into the smallest outline around it
+// a hole without any outline around it can't come out of filled and is dropped
+fn regions(rings: Vec<Polygon>) -> Vec<Region> {
+    let (outlines, holes): (Vec<_>, Vec<_>) = rings
+        .into_iter()
+        .partition(|ring| ring.winding() == Winding::CounterClockwise);
+    let mut regions: Vec<Region> = outlines
+        .into_iter()
+        .map(|outline| Region {
+            outline,
+            holes: Vec::new(),
+        })
+        .collect();
+    for hole in holes {
+        let Some(point) = hole.inner_point() else {
+            continue;
+        };
+        let around = regions
+            .iter_mut()
+            .filter(|region| region.outline.contains(point))
+            .min_by(|a, b| a.outline.area().total_cmp(&b.outline.area()));
+        if let Some(region) = around {
+            region.holes.push(hole);
+        }
+    }
+    regions
+}
+
+// joins directed segments end to start into closed rings
+// None if a ring doesn't close
+fn chain(mut segments: Vec<Line>) -> Option<Vec<Polygon>> {
+    let mut polygons = Vec::new();
+    while let Some(first) = segments.pop() {
+        let mut points = vec![first.get_a()];
+        let mut end = first.get_b();
+        while !end.approx_eq(points[0], EPSILON) {
+            let next = segments
+                .iter()
+                .position(|segment| segment.get_a().approx_eq(end, EPSILON))?;
+            points.push(end);
+            end = segments.swap_remove(next).get_b();
+        }
+        if points.len() >= 3 {
+            polygons.push(Polygon::new(points));
+        }
+    }
+    Some(polygons)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn square(x: f32, y: f32, size: f32) -> Polygon {
+        Polygon::rectangle(Float2::new(x, y), Float2::new(x + size, y + size))
+    }
+
+    fn area(regions: &[Region]) -> f32 {
+        regions.iter().map(Region::area).sum()
+    }
+
+    // L shaped, the corner at (1, 1) is concave
+    fn ell() -> Polygon {
+        Polygon::new(vec![
+            Float2::new(0.0, 0.0),
+            Float2::new(2.0, 0.0),
+            Float2::new(2.0, 1.0),
+            Float2::new(1.0, 1.0),
+            Float2::new(1.0, 2.0),
+            Float2::new(0.0, 2.0),
+        ])
+    }
+
+    #[test]
+    fn measures_and_locates() {
+        let ell = ell();
+        assert_eq!(ell.area(), 3.0);
+        assert_eq!(ell.winding(), Winding::CounterClockwise);
+        assert_eq!(ell.reversed().signed_area(), -3.0);
+        assert!(!ell.is_convex() && square(0.0, 0.0, 1.0).is_convex());
+        assert_eq!(ell.locate(Float2::new(0.5, 1.5)), Location::Inside);
+        assert_eq!(ell.locate(Float2::new(1.5, 1.5)), Location::Outside);
+        assert_eq!(ell.locate(Float2::new(1.0, 1.5)), Location::Boundary);
+        assert_eq!(ell.winding_number(Float2::new(0.5, 0.5)), 1);
+        assert_eq!(ell.reversed().winding_number(Float2::new(0.5, 0.5)), -1);
+    }
+
+    #[test]
+    fn union_of_overlapping_squares() {
+        let union = square(0.0, 0.0, 2.0).union(&square(1.0, 1.0, 2.0)).unwrap();
+        assert_eq!(union.len(), 1);
+        assert!((area(&union) - 7.0).abs() < 1e-4);
+        assert!(union[0].holes.is_empty());
+    }
+
+    #[test]
+    fn union_of_touching_squares() {
+        let union = square(0.0, 0.0, 1.0).union(&square(1.0, 0.0, 1.0)).unwrap();
+        assert_eq!(union.len(), 1);
+        assert!((area(&union) - 2.0).abs() < 1e-4);
+        // the shared edge is gone
+        assert_eq!(
+            union[0].outline.locate(Float2::new(1.0, 0.5)),
+            Location::Inside
+        );
+    }
+
+    #[test]
+    fn union_of_separate_and_nested_squares() {
+        let apart = square(0.0, 0.0, 1.0).union(&square(5.0, 5.0, 1.0)).unwrap();
+        assert_eq!(apart.len(), 2);
+        let nested = square(0.0, 0.0, 4.0).union(&square(1.0, 1.0, 1.0)).unwrap();
+        assert_eq!(nested.len(), 1);
+        assert!((area(&nested) - 16.0).abs() < 1e-4);
+    }
+
+    #[test]
+    fn difference_leaves_a_hole() {
+        let region = square(0.0, 0.0, 4.0)
+            .difference(&square(1.0, 1.0, 1.0))
+            .unwrap();
+        assert_eq!(region.len(), 1);
+        assert_eq!(region[0].holes.len(), 1);
+        assert_eq!(region[0].holes[0].winding(), Winding::Clockwise);
+        assert!((area(&region) - 15.0).abs() < 1e-4);
+        assert!(!region[0].contains(Float2::new(1.5, 1.5)));
+        assert!(region[0].contains(Float2::new(1.0, 1.5)));
+        assert!(region[0].contains(Float2::new(3.0, 3.0)));
+    }
+
+    #[test]
+    fn difference_of_overlapping_and_concave() {
+        let cut = square(0.0, 0.0, 2.0)
+            .difference(&square(1.0, 1.0, 2.0))
+            .unwrap();
+        assert_eq!(cut.len(), 1);
+        assert!((area(&cut) - 3.0).abs() < 1e-4);
+        // the notch of the L is filled by the square, only its overhang remains
+        let notch = square(1.0, 1.0, 2.0).difference(&ell()).unwrap();
+        assert!((area(&notch) - 4.0).abs() < 1e-4);
+        let gone = square(0.0, 0.0, 1.0)
+            .difference(&square(-1.0, -1.0, 3.0))
+            .unwrap();
+        assert!(gone.is_empty());
+    }
+
+    #[test]
+    fn offsets_concave_polygons() {
+        // the outer corners get mitered, the concave one moves inwards
+        let grown = ell().offset(0.1).unwrap();
+        assert_eq!(grown.len(), 1);
+        assert!(grown[0].outline.contains(Float2::new(-0.09, -0.09)));
+        assert!(!grown[0].outline.contains(Float2::new(1.2, 1.2)));
+        assert!((area(&grown) - 3.84).abs() < 1e-3);
+
+        let shrunk = ell().offset(-0.1).unwrap();
+        assert!((area(&shrunk) - 2.24).abs() < 1e-3);
+    }
+
+    #[test]
+    fn offset_cuts_away_loops() {
+        // a thin bar of width 1 shrinks by more than half its width on one end
+        // while a wide block on the other end survives
+        let dumbbell = Polygon::new(vec![
+            Float2::new(0.0, 0.0),
+            Float2::new(10.0, 0.0),
+            Float2::new(10.0, 1.0),
+            Float2::new(14.0, 1.0),
+            Float2::new(14.0, -3.0),
+            Float2::new(20.0, -3.0),
+            Float2::new(20.0, 3.0),
+            Float2::new(14.0, 3.0),
+            Float2::new(14.0, 2.0),
+            Float2::new(10.0, 2.0),
+            Float2::new(10.0, 3.0),
+            Float2::new(0.0, 3.0),
+        ]);
+        let shrunk = dumbbell.offset(-0.6).unwrap();
+        assert_eq!(shrunk.len(), 2);
+        for region in &shrunk {
+            assert!(region.area() > 0.0);
+            assert_eq!(region.outline.winding(), Winding::CounterClockwise);
+        }
+        assert!(dumbbell.offset(-5.0).unwrap().is_empty());
+    }
+
+    #[test]
+    fn open_chains_are_rejected() {
+        let open = vec![
+            Line::new(Float2::new(0.0, 0.0), Float2::new(1.0, 0.0)),
+            Line::new(Float2::new(1.0, 0.0), Float2::new(1.0, 1.0)),
+        ];
+        assert_eq!(chain(open), None);
+    }
+
+    #[test]
+    fn sweeps_into_lines() {
+        let square = square(0.0, 0.0, 10.0);
+        let wall = Line::new(Float2::new(-5.0, 20.0), Float2::new(15.0, 20.0));
+        // the top edge runs into the wall, after 10 of 20
+        assert_eq!(square.sweep(Float2::new(0.0, 20.0), &wall), Some(0.5));
+        assert_eq!(square.sweep(Float2::new(0.0, 5.0), &wall), None);
+        // the end of a wall runs into the side of the square
+        let post = Line::new(Float2::new(20.0, 5.0), Float2::new(30.0, 5.0));
+        assert_eq!(square.sweep(Float2::new(20.0, 0.0), &post), Some(0.5));
+        // a long step doesn't pass through
+        assert!(square.sweep(Float2::new(0.0, 1000.0), &wall).is_some());
+    }
+
+    #[test]
+    fn touching_polygons_only_stop_moving_in() {
+        let square = square(0.0, 0.0, 10.0);
+        // the top edge lies on the wall
+        let wall = Line::new(Float2::new(-5.0, 10.0), Float2::new(15.0, 10.0));
+        assert_eq!(square.sweep(Float2::new(0.0, 1.0), &wall), Some(0.0));
+        assert_eq!(square.sweep(Float2::new(1.0, 0.1), &wall), Some(0.0));
+        assert_eq!(square.sweep(Float2::new(1.0, 0.0), &wall), None);
+        assert_eq!(square.sweep(Float2::new(0.0, -1.0), &wall), None);
+        // the end of a wall against the right side
+        let post = Line::new(Float2::new(10.0, 5.0), Float2::new(20.0, 5.0));
+        assert_eq!(square.sweep(Float2::new(1.0, 0.0), &post), Some(0.0));
+        assert_eq!(square.sweep(Float2::new(0.0, 1.0), &post), None);
+        assert_eq!(square.sweep(Float2::new(-1.0, 0.0), &post), None);
+        // a wall through the middle stays stuck
+        let through = Line::new(Float2::new(-5.0, 5.0), Float2::new(15.0, 5.0));
+        assert_eq!(square.sweep(Float2::new(0.0, -1.0), &through), Some(0.0));
+    }
+}
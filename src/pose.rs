@@ -0,0 +1,93 @@
+use crate::angle::Angle;
+use crate::float2::Float2;
+
+// position = mm
+// heading = counter clockwise from the x axis
+// a pose is also the transform from its own frame into the parent frame
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Pose {
+    pub position: Float2,
+    pub heading: Angle,
+}
+
+impl Pose {
+    pub fn new(position: Float2, heading: Angle) -> Self {
+        Self { position, heading }
+    }
+
+    pub fn identity() -> Self {
+        Self::new(Float2::ZERO, Angle::ZERO)
+    }
+
+    // + = counter clockwise; - = clockwise
+    pub fn rotate(&mut self, angle: Angle) {
+        self.heading += angle;
+    }
+
+    // unit vector in heading direction
+    pub fn forward(&self) -> Float2 {
+        self.heading.to_vector()
+    }
+
+    // other is given in the frame of self
+    pub fn compose(&self, other: Pose) -> Pose {
+        Pose::new(
+            self.transform_point(other.position),
+            self.heading + other.heading,
+        )
+    }
+
+    pub fn inverse(&self) -> Pose {
+        Pose::new(-self.position.rotate(-self.heading), -self.heading)
+    }
+
+    // local frame -> parent frame
+    pub fn transform_point(&self, point: Float2) -> Float2 {
+        self.position + point.rotate(self.heading)
+    }
+
+    // parent frame -> local frame
+    pub fn inverse_transform_point(&self, point: Float2) -> Float2 {
+        (point - self.position).rotate(-self.heading)
+    }
+
+    // the heading turns the short way round
+    // t = 0 -> self; t = 1 -> other
+    pub fn interpolate(&self, other: Pose, t: f32) -> Pose {
+        Pose::new(
+            self.position.lerp(other.position, t),
+            self.heading.lerp(other.heading, t),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn composes_with_its_inverse() {
+        let pose = Pose::new(Float2::new(100.0, -50.0), Angle::from_degrees(30.0));
+        let point = Float2::new(20.0, 70.0);
+        let world = pose.transform_point(point);
+        assert!(pose.inverse_transform_point(world).approx_eq(point, 1e-3));
+        assert!(pose.inverse().transform_point(world).approx_eq(point, 1e-3));
+
+        let identity = pose.compose(pose.inverse());
+        assert!(identity.position.approx_eq(Float2::ZERO, 1e-3));
+        assert!(identity
+            .heading
+            .approx_eq(Angle::ZERO, Angle::from_degrees(1e-3)));
+    }
+
+    #[test]
+    fn interpolates_the_short_way_round() {
+        let a = Pose::new(Float2::ZERO, Angle::from_degrees(170.0));
+        let b = Pose::new(Float2::new(100.0, 0.0), Angle::from_degrees(-170.0));
+        let half = a.interpolate(b, 0.5);
+        assert!(half.position.approx_eq(Float2::new(50.0, 0.0), 1e-6));
+        assert!(half
+            .heading
+            .approx_eq(Angle::from_degrees(180.0), Angle::from_degrees(1e-3)));
+    }
+}
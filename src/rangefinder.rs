@@ -0,0 +1,125 @@
+use crate::angle::Angle;
+use crate::float2::Float2;
+use crate::lidar::cast_ray;
+use crate::pose::Pose;
+use crate::sensor::{Mount, Sensor};
+use crate::units::Length;
+use crate::world::World;
+
+use std::time::Duration;
+
+// Linear = reading in mm
+// Ir = output voltage a / (distance in mm + b), like the sharp GP2Y0A sensors
+#[allow(dead_code)]
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Response {
+    Linear,
+    Ir { a: f32, b: f32 },
+}
+
+impl Response {
+    fn output(&self, distance: Length) -> f32 {
+        match self {
+            Response::Linear => distance.mm(),
+            Response::Ir { a, b } => a / (distance.mm() + b),
+        }
+    }
+
+    fn inverse(&self, reading: f32) -> Length {
+        match self {
+            Response::Linear => Length::from_mm(reading),
+            Response::Ir { a, b } => Length::from_mm(a / reading - b),
+        }
+    }
+}
+
+// beam_width = opening of the cone
+// rate = Hz
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Rangefinder {
+    pub mount: Mount,
+    pub beam_width: Angle,
+    pub ray_count: usize,
+    pub min_range: Length,
+    pub max_range: Length,
+    pub response: Response,
+    pub rate: f32,
+}
+
+impl Rangefinder {
+    // short range ir sensor on the right side, looking 20deg ahead to the wall
+    pub fn side_ir(radius: Length) -> Self {
+        Self {
+            mount: Mount::new(
+                Float2::new(0.0, -(radius.mm() - 10.0)),
+                Angle::from_degrees(290.0),
+            )
+            .with_height(Length::from_mm(30.0)),
+            beam_width: Angle::from_degrees(10.0),
+            ray_count: 5,
+            min_range: Length::from_mm(5.0),
+            max_range: Length::from_mm(50.0),
+            response: Response::Ir { a: 60.0, b: 10.0 },
+            rate: 100.0,
+        }
+    }
+
+    // closest wall inside the cone
+    // position and direction of the sensor itself
+    // only walls crossing the mount height are seen
+    pub fn measure(&self, world: &World, pose: Pose) -> Option<Length> {
+        (0..self.ray_count)
+            .filter_map(|num| {
+                let offset = if self.ray_count > 1 {
+                    self.beam_width * (num as f32 / (self.ray_count - 1) as f32 - 0.5)
+                } else {
+                    Angle::ZERO
+                };
+                let ray = (pose.heading + offset).to_vector();
+                cast_ray(world, pose.position, ray, self.max_range.mm(), |wall, _| {
+                    wall.spans(self.mount.height)
+                })
+                .map(|hit| hit.distance)
+            })
+            .min_by(|a, b| a.mm().total_cmp(&b.mm()))
+    }
+
+    // raw sensor output for a measured distance
+    pub fn reading(&self, distance: Option<Length>) -> f32 {
+        match distance {
+            // too close, the ir output folds back and looks like a far wall
+            Some(distance) if distance < self.min_range => {
+                self.response.output(self.min_range) * (distance / self.min_range)
+            }
+            Some(distance) => self.response.output(distance),
+            None => self.response.output(self.max_range),
+        }
+    }
+
+    // distance from the raw sensor output, as the firmware would convert it
+    pub fn distance(&self, reading: f32) -> Option<Length> {
+        let distance = self.response.inverse(reading);
+        if distance.mm().is_finite() && distance < self.max_range {
+            Some(distance.max(self.min_range))
+        } else {
+            None
+        }
+    }
+}
+
+// the reading is the raw sensor output
+impl Sensor for Rangefinder {
+    type Reading = f32;
+
+    fn mount(&self) -> Mount {
+        self.mount
+    }
+
+    fn rate(&self) -> f32 {
+        self.rate
+    }
+
+    fn update(&mut self, world: &World, pose: Pose, _time: Duration) -> Option<f32> {
+        Some(self.reading(self.measure(world, pose)))
+    }
+}
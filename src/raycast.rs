@@ -0,0 +1,155 @@
+use crate::float2::Float2;
+use crate::grid::Grid;
+use crate::line::Line;
+
+use std::thread;
+
+// smaller batches are cast on the calling thread, e.g. a tick of the default lidar,
+// spawning costs more than it saves there
+const MIN_RAYS_FOR_THREADS: usize = 1024;
+// with this few walls testing every one of them over the slices beats walking the grid
+const MAX_WALLS_WITHOUT_GRID: usize = 32;
+
+// direction = unit vector in world coordinates
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Ray {
+    pub origin: Float2,
+    pub direction: Float2,
+    pub max_dist: f32,
+}
+
+// wall start points and directions as separate arrays,
+// so the intersection math runs over plain f32 slices
+#[derive(Debug, Clone, Default)]
+pub struct WallArrays {
+    ax: Vec<f32>,
+    ay: Vec<f32>,
+    dx: Vec<f32>,
+    dy: Vec<f32>,
+}
+
+impl WallArrays {
+    pub fn new(walls: &[Line]) -> Self {
+        let mut arrays = Self::default();
+        walls.iter().for_each(|wall| arrays.push(wall));
+        arrays
+    }
+
+    pub fn push(&mut self, wall: &Line) {
+        let direction = wall.get_b() - wall.get_a();
+        self.ax.push(wall.get_a().get_x());
+        self.ay.push(wall.get_a().get_y());
+        self.dx.push(direction.get_x());
+        self.dy.push(direction.get_y());
+    }
+
+    pub fn set(&mut self, index: usize, wall: &Line) {
+        let direction = wall.get_b() - wall.get_a();
+        self.ax[index] = wall.get_a().get_x();
+        self.ay[index] = wall.get_a().get_y();
+        self.dx[index] = direction.get_x();
+        self.dy[index] = direction.get_y();
+    }
+
+    // same operations in the same order as utils::intersection_distance,
+    // so both give bit identical results
+    pub fn distance(&self, index: usize, origin: Float2, ray: Float2) -> Option<f32> {
+        let (dx, dy) = (self.dx[index], self.dy[index]);
+        let to_x = self.ax[index] - origin.get_x();
+        let to_y = self.ay[index] - origin.get_y();
+
+        let denom = ray.get_x() * dy - ray.get_y() * dx;
+
+        let t = (to_x * dy - to_y * dx) / denom;
+        let s = (to_x * ray.get_y() - to_y * ray.get_x()) / denom;
+
+        if t >= 0.0 && (0.0..=1.0).contains(&s) {
+            Some(t)
+        } else {
+            None
+        }
+    }
+
+    // distance along the ray to every wall, NaN where it misses
+    // one pass over the slices without branching on the walls, so it vectorizes
+    // the operations are the same as in distance, so are the results
+    pub fn distances(&self, origin: Float2, ray: Float2, distances: &mut [f32]) {
+        let (ox, oy) = (origin.get_x(), origin.get_y());
+        let (rx, ry) = (ray.get_x(), ray.get_y());
+        let walls = self.ax.iter().zip(&self.ay).zip(&self.dx).zip(&self.dy);
+        for (distance, (((ax, ay), dx), dy)) in distances.iter_mut().zip(walls) {
+            let to_x = ax - ox;
+            let to_y = ay - oy;
+
+            let denom = rx * dy - ry * dx;
+
+            let t = (to_x * dy - to_y * dx) / denom;
+            let s = (to_x * ry - to_y * rx) / denom;
+
+            *distance = if t >= 0.0 && (0.0..=1.0).contains(&s) {
+                t
+            } else {
+                f32::NAN
+            };
+        }
+    }
+}
+
+// closest visible wall for every ray as (index, distance), in the order of the rays
+// large batches are split into chunks cast on scoped threads
+// every ray is cast exactly like a single one, so the results don't depend on the threads
+pub fn cast_batch<F>(
+    walls: &[Line],
+    arrays: &WallArrays,
+    grid: &Grid,
+    rays: &[Ray],
+    visible: F,
+) -> Vec<Option<(usize, f32)>>
+where
+    F: Fn(&Line, f32) -> bool + Sync,
+{
+    // distances is scratch space for the walls, kept per thread
+    let cast = |ray: &Ray, distances: &mut Vec<f32>| {
+        let seen = |index: usize, distance: f32| {
+            distance <= ray.max_dist && visible(&walls[index], distance)
+        };
+        if walls.len() > MAX_WALLS_WITHOUT_GRID {
+            return grid.cast(ray.origin, ray.direction, ray.max_dist, |index| {
+                arrays
+                    .distance(index, ray.origin, ray.direction)
+                    .filter(|distance| seen(index, *distance))
+            });
+        }
+        distances.resize(walls.len(), f32::NAN);
+        arrays.distances(ray.origin, ray.direction, distances);
+        // ties go to the lower index, like in Grid::cast
+        let mut closest: Option<(usize, f32)> = None;
+        for (index, distance) in distances.iter().enumerate() {
+            if closest.is_none_or(|(_, best)| *distance < best) && seen(index, *distance) {
+                closest = Some((index, *distance));
+            }
+        }
+        closest
+    };
+
+    if rays.len() < MIN_RAYS_FOR_THREADS {
+        let mut distances = Vec::new();
+        return rays.iter().map(|ray| cast(ray, &mut distances)).collect();
+    }
+
+    let threads = thread::available_parallelism().map_or(1, |threads| threads.get());
+    let chunk_size = rays.len().div_ceil(threads);
+    let mut hits = vec![None; rays.len()];
+    thread::scope(|scope| {
+        for (rays, hits) in rays.chunks(chunk_size).zip(hits.chunks_mut(chunk_size)) {
+            let cast = &cast;
+            scope.spawn(move || {
+                let mut distances = Vec::new();
+                for (ray, hit) in rays.iter().zip(hits.iter_mut()) {
+                    *hit = cast(ray, &mut distances);
+                }
+            });
+        }
+    });
+    hits
+}
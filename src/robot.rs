@@ -1,14 +1,50 @@
+use crate::angle::Angle;
+use crate::bumper::Bumper;
+use crate::depth_camera::DepthCamera;
+use crate::dirt::{DirtSensor, Surface};
+use crate::fault::{FaultKind, Scenario, Wheel};
 use crate::float2::Float2;
-use crate::line::Line;
-use crate::utils::{direction_to_vector, intersection_distance, run_with_interval};
-
-use std::sync::atomic::AtomicBool;
+use crate::frames::{FrameId, FrameTree, WORLD};
+use crate::gyro::Gyro;
+use crate::lidar::{Lidar, LidarSensor, Scan};
+use crate::pose::Pose;
+use crate::rangefinder::Rangefinder;
+use crate::sensor::{Latency, Mount, Sensor, SensorId, SensorRegistry};
+use crate::units::{AngularSpeed, Length, Speed};
+use crate::utils::run_with_interval;
+use crate::world::World;
+
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::{Arc, Mutex};
 use std::thread::{self, JoinHandle};
-use std::time::Duration;
-
-const WALL_MAX_DIST: f32 = 50.0;
-const LIDAR_MAX_DIST: f32 = 10000.0;
+use std::time::{Duration, Instant};
+
+const DIRT_DETECT_THRESHOLD: f32 = 5.0; // g/m²
+const SPOT_CLEAN_TIME: Duration = Duration::from_secs(15);
+const STUCK_SPEED_RATIO: f32 = 0.2; // actual / commanded speed
+const STUCK_TIME: Duration = Duration::from_secs(1);
+const ESCAPE_BACKUP_TIME: Duration = Duration::from_millis(500);
+const ESCAPE_TURN_TIME: Duration = Duration::from_millis(1500);
+const HEADING_TOLERANCE: Angle = Angle::from_degrees(0.5);
+const LIDAR_HEIGHT: Length = Length::from_mm(90.0);
+const BUMPER_HEIGHT: (Length, Length) = (Length::from_mm(10.0), Length::from_mm(70.0));
+const HISTORY_LENGTH: Duration = Duration::from_secs(2);
+const LIDAR_TIMEOUT: Duration = Duration::from_secs(1);
+const WHEEL_BASE: Length = Length::from_mm(230.0);
+const WALL_SETPOINT: Length = Length::from_mm(21.5); // from the wall sensor ~ 10mm from the body
+const WALL_GAP: Length = Length::from_mm(10.0); // from the body, held by the setpoint
+const RNG_SEED: u64 = 42;
+const OBSTACLE_DISTANCE: Length = Length::from_mm(60.0); // from the body, the depth camera stops
+const CONTACT_GAP: Length = Length::from_mm(0.5); // kept to a wall the robot runs into
+const BUMPER_REACH: Length = Length::from_mm(2.0); // the shell sticks out beyond the contact gap
+
+// which sensors the robot carries, to compare how they do
+// the lidar is always on board, the behaviours need it to find walls
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum SensorSuite {
+    Lidar,
+    LidarAndDepthCamera,
+}
 
 pub enum Direction {
     Forward,
@@ -16,263 +52,966 @@ pub enum Direction {
     None,
 }
 
+// immutable copy of the robot for the viewer
+// frames = world pose of every frame of the robot
+// frame_names = names of the frames, only rebuilt when a frame gets added
+// wall_error = rms of the true gap to the wall against WALL_GAP while following it,
+// None before the robot followed a wall
+// the lidar scan is in the lidar frame
 pub struct RobotState {
-    pub direction: f32,
-    pub lidar: Vec<f32>,
-    pub position: Float2,
-    pub radius: f32,
+    pub pose: Pose,
+    pub lidar: Arc<Scan>,
+    pub radius: Length,
+    pub frames: Vec<(FrameId, Pose)>,
+    pub frame_names: Arc<Vec<(FrameId, String)>>,
+    pub lidar_frame: FrameId,
+    pub slip: f32,
+    pub wall_error: Option<Length>,
+}
+
+impl RobotState {
+    pub fn frame_name(&self, id: FrameId) -> &str {
+        self.frame_names
+            .iter()
+            .find(|(other, _)| *other == id)
+            .map_or("", |(_, name)| name)
+    }
+
+    pub fn frame_pose(&self, id: FrameId) -> Option<Pose> {
+        self.frames
+            .iter()
+            .find(|(other, _)| *other == id)
+            .map(|(_, pose)| *pose)
+    }
+}
+
+// the robot swaps in a new state every tick, readers only clone the Arc
+// so neither side waits for the other to finish its work
+#[derive(Clone)]
+pub struct StateHandle(Arc<Mutex<Arc<RobotState>>>);
+
+impl StateHandle {
+    fn new(state: RobotState) -> Self {
+        Self(Arc::new(Mutex::new(Arc::new(state))))
+    }
+
+    pub fn load(&self) -> Arc<RobotState> {
+        Arc::clone(&self.0.lock().unwrap())
+    }
+
+    fn store(&self, state: RobotState) {
+        *self.0.lock().unwrap() = Arc::new(state);
+    }
 }
 
-// speed = mm/s
-// rotation_speed = degree/s
+// height = top of the body, clearance = bottom of the body, both above the floor
 pub struct Robot {
-    state: Arc<Mutex<RobotState>>,
-    speed: f32,
-    rotation_speed: f32,
-    sensor_collision: bool,
-    sensor_wall: f32,
+    pose: Pose,
+    radius: Length,
+    scan: Arc<Scan>,
+    state: StateHandle,
+    height: Length,
+    clearance: Length,
+    speed: Speed,
+    rotation_speed: AngularSpeed,
+    sensors: SensorRegistry,
+    lidar: SensorId<LidarSensor>,
+    bumper: SensorId<Bumper>,
+    wall_sensor: SensorId<Rangefinder>,
+    dirt_sensor: SensorId<DirtSensor>,
+    gyro: SensorId<Gyro>,
+    depth_camera: Option<SensorId<DepthCamera>>,
+    heading: Angle,
+    gyro_stamp: Duration,
+    scan_stamp: Duration,
+    scan_arrival: Duration,
+    scan_fresh: bool,
+    frozen_scan: Option<Scan>,
+    scenario: Scenario,
+    faults: Vec<FaultKind>,
+    frames: FrameTree,
+    frame_names: Arc<Vec<(FrameId, String)>>,
+    odom: FrameId,
+    base_link: FrameId,
+    lidar_frame: FrameId,
+    slip: f32,
+    wall_error_sum: f32, // mm²
+    wall_error_ticks: u32,
+    sensor_stuck: bool,
+    stuck_time: Duration,
+    surface: Surface,
+    start: Instant,
     interval: Duration,
 }
 
 impl Robot {
-    pub fn new(x: f32, y: f32) -> Self {
-        let state = RobotState {
-            direction: 0.0, // eastside
-            lidar: vec![0.0; 360],
-            position: Float2::new(x, y),
-            radius: 175.0, // mm
-        };
-        Self {
-            state: Arc::new(Mutex::new(state)),
-            speed: 400.0,
-            rotation_speed: 60.0,
-            sensor_collision: false,
-            sensor_wall: 0.0,
+    pub fn new(x: Length, y: Length, suite: SensorSuite) -> Self {
+        let lidar = Lidar::default();
+        let pose = Pose::new(Float2::new(x.mm(), y.mm()), Angle::ZERO); // eastside
+        let radius = Length::from_mm(175.0);
+        let scan = Arc::new(lidar.empty_scan());
+
+        let mut sensors = SensorRegistry::new();
+        let lidar = sensors.add(LidarSensor::new(
+            lidar,
+            Mount::center().with_height(LIDAR_HEIGHT),
+            RNG_SEED,
+        ));
+        let bumper = sensors.add(Bumper {
+            radius: radius + BUMPER_REACH,
+            z_min: BUMPER_HEIGHT.0,
+            z_max: BUMPER_HEIGHT.1,
+        });
+        let wall_sensor = sensors.add(Rangefinder::side_ir(radius));
+        let dirt_sensor = sensors.add(DirtSensor {
+            threshold: DIRT_DETECT_THRESHOLD,
+        });
+        let gyro = sensors.add(Gyro::new(AngularSpeed::default()));
+
+        let mut frames = FrameTree::new(HISTORY_LENGTH);
+        let odom = frames.add_dynamic("odom", WORLD);
+        let base_link = frames.add_dynamic("base_link", odom);
+        let lidar_frame = frames.add_static(
+            "lidar",
+            base_link,
+            Sensor::mount(sensors.sensor(lidar)).pose(),
+        );
+        frames.add_static(
+            "bumper",
+            base_link,
+            Sensor::mount(sensors.sensor(bumper)).pose(),
+        );
+        frames.add_static(
+            "wall_sensor",
+            base_link,
+            Sensor::mount(sensors.sensor(wall_sensor)).pose(),
+        );
+        frames.add_static(
+            "dirt_sensor",
+            base_link,
+            Sensor::mount(sensors.sensor(dirt_sensor)).pose(),
+        );
+        frames.add_static(
+            "gyro",
+            base_link,
+            Sensor::mount(sensors.sensor(gyro)).pose(),
+        );
+        let frame_names = Self::frame_names(&frames);
+        let state = StateHandle::new(RobotState {
+            pose,
+            lidar: Arc::clone(&scan),
+            radius,
+            frames: Vec::new(),
+            frame_names: Arc::clone(&frame_names),
+            lidar_frame,
+            slip: 0.0,
+            wall_error: None,
+        });
+
+        let mut robot = Self {
+            pose,
+            radius,
+            scan,
+            state,
+            height: Length::from_mm(95.0),
+            clearance: Length::from_mm(8.0),
+            speed: Speed::from_mm_per_s(400.0),
+            rotation_speed: AngularSpeed::from_degrees_per_s(60.0),
+            sensors,
+            lidar,
+            bumper,
+            wall_sensor,
+            dirt_sensor,
+            gyro,
+            depth_camera: None,
+            heading: Angle::ZERO,
+            gyro_stamp: Duration::ZERO,
+            scan_stamp: Duration::ZERO,
+            scan_arrival: Duration::ZERO,
+            scan_fresh: false,
+            frozen_scan: None,
+            scenario: Scenario::new(),
+            faults: Vec::new(),
+            frames,
+            frame_names,
+            odom,
+            base_link,
+            lidar_frame,
+            slip: 0.0,
+            wall_error_sum: 0.0,
+            wall_error_ticks: 0,
+            sensor_stuck: false,
+            stuck_time: Duration::ZERO,
+            surface: Surface::Hardfloor,
+            start: Instant::now(),
             interval: Duration::from_millis(10),
+        };
+        let ms = Duration::from_millis;
+        robot.set_latency(lidar, Latency::new(ms(20), ms(30)));
+        robot.set_latency(bumper, Latency::new(ms(5), ms(2)));
+        robot.set_latency(wall_sensor, Latency::new(ms(2), ms(1)));
+        robot.set_latency(dirt_sensor, Latency::new(ms(10), ms(5)));
+        if suite == SensorSuite::LidarAndDepthCamera {
+            let camera = robot.add_sensor("depth_camera", DepthCamera::front(radius));
+            robot.depth_camera = Some(camera);
         }
+        robot
     }
 
-    pub fn get_state(&self) -> Arc<Mutex<RobotState>> {
-        Arc::clone(&self.state)
+    pub fn get_state(&self) -> StateHandle {
+        self.state.clone()
     }
 
-    fn scan_lidar(&mut self, room: &Arc<Vec<Line>>) {
-        let mut state = self.state.lock().unwrap();
-        let direction = state.direction;
-        let position = state.position;
-        state
-            .lidar
-            .iter_mut()
-            .enumerate()
-            .for_each(|(num, distance)| {
-                let ray = direction_to_vector(num as f32 + direction);
-                let mut closest = LIDAR_MAX_DIST;
-                room.iter().for_each(|wall| {
-                    let distance = intersection_distance(position, ray, *wall);
-                    if distance < closest {
-                        closest = distance;
-                    }
+    fn frame_names(frames: &FrameTree) -> Arc<Vec<(FrameId, String)>> {
+        Arc::new(
+            frames
+                .ids()
+                .map(|id| (id, frames.name(id).to_string()))
+                .collect(),
+        )
+    }
+
+    fn publish(&self, time: Duration) {
+        let frames = self
+            .frames
+            .ids()
+            .filter_map(|id| Some((id, self.frames.world_pose(id, time)?)))
+            .collect();
+        self.state.store(RobotState {
+            pose: self.pose,
+            lidar: Arc::clone(&self.scan),
+            radius: self.radius,
+            frames,
+            frame_names: Arc::clone(&self.frame_names),
+            lidar_frame: self.lidar_frame,
+            slip: self.slip,
+            wall_error: (self.wall_error_ticks > 0).then(|| {
+                Length::from_mm((self.wall_error_sum / self.wall_error_ticks as f32).sqrt())
+            }),
+        });
+    }
+
+    // custom sensors get updated together with the built in ones
+    // the name is used for the frame of the sensor
+    pub fn add_sensor<S: Sensor + 'static>(&mut self, name: &str, sensor: S) -> SensorId<S> {
+        self.frames
+            .add_static(name, self.base_link, sensor.mount().pose());
+        self.frame_names = Self::frame_names(&self.frames);
+        self.sensors.add(sensor)
+    }
+
+    // latest delivered reading, None until the first one arrived
+    pub fn reading<S: Sensor + 'static>(&self, id: SensorId<S>) -> Option<&S::Reading> {
+        self.sensors.reading(id)
+    }
+
+    // capture time of the latest delivered reading, since the robot started
+    pub fn stamp<S>(&self, id: SensorId<S>) -> Duration {
+        self.sensors.stamp(id)
+    }
+
+    // true if a reading got delivered in the last tick
+    pub fn is_fresh<S>(&self, id: SensorId<S>) -> bool {
+        self.sensors.is_fresh(id)
+    }
+
+    // where the robot believes it is, the direction comes from the gyro
+    fn estimate(&self) -> Pose {
+        Pose::new(self.pose.position, self.heading)
+    }
+
+    pub fn set_latency<S>(&mut self, id: SensorId<S>, latency: Latency) {
+        self.sensors.set_latency(id, latency);
+    }
+
+    // faults are scheduled relative to the start of the robot
+    pub fn set_scenario(&mut self, scenario: Scenario) {
+        self.scenario = scenario;
+    }
+
+    pub fn set_lidar(&mut self, lidar: Lidar) {
+        self.sensors.sensor_mut(self.lidar).set_config(lidar);
+        self.scan = Arc::new(lidar.empty_scan());
+    }
+
+    // updates every sensor that is due with the current pose
+    // active faults are applied to the readings
+    // the state of the last tick is published first, the sensors are computed without any lock
+    fn sense(&mut self, world: &World) {
+        self.sense_at(world, self.start.elapsed());
+    }
+
+    // time = since the robot started, given explicitly so tests don't depend on the clock
+    fn sense_at(&mut self, world: &World, time: Duration) {
+        self.publish(time);
+        let pose = self.pose;
+
+        // looked up once per tick, the motion uses them too
+        self.faults.clear();
+        self.faults.extend(self.scenario.active(time));
+        let faults = &self.faults;
+        self.sensors.update(world, pose, time);
+
+        // the robot only knows its direction from the gyro
+        if self.is_fresh(self.gyro) {
+            let drift = faults
+                .iter()
+                .fold(AngularSpeed::default(), |sum, fault| match fault {
+                    FaultKind::GyroDrift(drift) => sum + *drift,
+                    _ => sum,
                 });
-                *distance = closest;
-            });
-    }
-
-    fn check_collision(&mut self, room: &Arc<Vec<Line>>) {
-        let state = self.state.lock().unwrap();
-        self.sensor_collision = false;
-        let pos_x = state.position.get_x();
-        let pos_y = state.position.get_y();
-        let radius = state.radius;
-        drop(state);
-
-        for wall in room.iter() {
-            let x1 = wall.get_a().get_x();
-            let x2 = wall.get_b().get_x();
-            let y1 = wall.get_a().get_y();
-            let y2 = wall.get_b().get_y();
-
-            // are the line endpoints inside the circle?
-            let inside1 = ((x1 - pos_x).powi(2) + (y1 - pos_y).powi(2)).sqrt() <= radius;
-            let inside2 = ((x2 - pos_x).powi(2) + (y2 - pos_y).powi(2)).sqrt() <= radius;
-            if inside1 || inside2 {
-                self.sensor_collision = true;
-                break;
-            }
+            let stamp = self.stamp(self.gyro);
+            let rate = self.reading(self.gyro).copied().unwrap_or_default() + drift;
+            self.heading += rate * (stamp - self.gyro_stamp);
+            self.gyro_stamp = stamp;
+        }
 
-            // length of the wall
-            let wall_len = ((x1 - x2).powi(2) + (y1 - y2).powi(2)).sqrt();
-
-            // dot product of wall and circle
-            let dot = (((pos_x - x1) * (x2 - x1)) + ((pos_y - y1) * (y2 - y1))) / wall_len.powi(2);
-
-            // find closest point on the wall
-            let closest_x = x1 + (dot * (x2 - x1));
-            let closest_y = y1 + (dot * (y2 - y1));
-
-            // check if closest point is on the line
-            // distances from collison point to line points
-            let d1 = ((x1 - closest_x).powi(2) + (y1 - closest_y).powi(2)).sqrt();
-            let d2 = ((x2 - closest_x).powi(2) + (y2 - closest_y).powi(2)).sqrt();
-
-            // check if sum(d1 + d2) - wall length == 0.0
-            // if so the point lays between the wall points
-            // 1e-9 instead of 0.0 for floating inacurassis
-            if (d1 + d2 - wall_len).abs() < 1e-9 {
-                let dist = ((pos_x - closest_x).powi(2) + (pos_y - closest_y).powi(2)).sqrt();
-                if dist <= radius {
-                    self.sensor_collision = true;
-                    break;
+        // odom absorbs the difference between the estimate and the true pose
+        let estimate = self.estimate();
+        self.frames.set_transform(self.base_link, time, estimate);
+        self.frames
+            .set_transform(self.odom, time, pose.compose(estimate.inverse()));
+
+        // scans overtaken by a newer one are dropped
+        let stamp = self.stamp(self.lidar);
+        self.scan_fresh = self.is_fresh(self.lidar)
+            && stamp > self.scan_stamp
+            && !faults.contains(&FaultKind::LidarStopped);
+        if !faults.contains(&FaultKind::FrozenScan) {
+            self.frozen_scan = None;
+        }
+        if self.scan_fresh {
+            self.scan_stamp = stamp;
+            self.scan_arrival = time;
+            if let Some(scan) = self.sensors.reading(self.lidar) {
+                if faults.contains(&FaultKind::FrozenScan) && self.frozen_scan.is_none() {
+                    self.frozen_scan = Some(scan.clone());
                 }
+                self.scan = Arc::new(self.frozen_scan.as_ref().unwrap_or(scan).clone());
+            }
+        }
+    }
+
+    fn new_scan(&self) -> Option<&Scan> {
+        if !self.scan_fresh {
+            return None;
+        }
+        match &self.frozen_scan {
+            Some(scan) => Some(scan),
+            None => self.reading(self.lidar),
+        }
+    }
+
+    // no scans for a while, behaviours fall back to the other sensors
+    fn lidar_timed_out(&self) -> bool {
+        self.start.elapsed() > self.scan_arrival + LIDAR_TIMEOUT
+    }
+
+    // estimated pose in odom at a past time, readings arrive late
+    fn pose_at(&self, time: Duration) -> Pose {
+        self.frames
+            .lookup(self.odom, self.base_link, time)
+            .unwrap_or_else(|| self.estimate())
+    }
+
+    // direction of a lidar ray in odom at the time it got captured
+    fn scan_direction(&self, scan: &Scan, num: usize) -> Angle {
+        let time = scan.timestamps[num];
+        let lidar = self
+            .frames
+            .lookup(self.odom, self.lidar_frame, time)
+            .unwrap_or_else(|| self.pose_at(time));
+        lidar.heading + scan.angle(num)
+    }
+
+    // distance driven since a past time
+    fn travelled_since(&self, time: Duration) -> Length {
+        Length::from_mm(
+            self.estimate()
+                .position
+                .distance(self.pose_at(time).position),
+        )
+    }
+
+    fn bumper_pressed(&self) -> bool {
+        for fault in &self.faults {
+            if let FaultKind::BumperStuck(pressed) = fault {
+                return *pressed;
             }
         }
+        self.reading(self.bumper).copied().unwrap_or(false)
+    }
+
+    // something in the path of the body seen by the depth camera, e.g. a cable below the lidar
+    fn obstacle_ahead(&self) -> bool {
+        let Some(camera) = self.depth_camera else {
+            return false;
+        };
+        let Some(image) = self.reading(camera) else {
+            return false;
+        };
+        let mount = self.sensors.sensor(camera).mount.pose();
+        image
+            .depths
+            .iter()
+            .enumerate()
+            .filter_map(|(column, depth)| depth.map(|depth| (column, depth)))
+            .any(|(column, depth)| {
+                let angle = image.angle_min + column as f32 * image.angle_increment;
+                let point =
+                    mount.transform_point(Angle::from_degrees(angle).to_vector() * depth.mm());
+                point.get_x() <= (self.radius + OBSTACLE_DISTANCE).mm()
+                    && point.get_y().abs() <= self.radius.mm()
+            })
     }
 
-    fn check_wall(&mut self, room: &Arc<Vec<Line>>) {
-        self.sensor_wall = 0.0;
-        let mut min_dist = WALL_MAX_DIST;
-        for wall in room.iter() {
-            let state = self.state.lock().unwrap();
-            let vector = direction_to_vector(state.direction + 290.0); // shoot the ray at an 20deg angle
-            let origin = state.position
-                + direction_to_vector(state.direction + 270.0) * (state.radius - 10.0);
-            let distance = intersection_distance(origin, vector, *wall);
-            if distance < min_dist {
-                min_dist = distance
-            };
-            self.sensor_wall = min_dist;
+    // speed factor of both wheels, reduced by motor faults
+    fn wheel_torque(&self) -> (f32, f32) {
+        let mut torque = (1.0, 1.0);
+        for fault in &self.faults {
+            match *fault {
+                FaultKind::WheelTorque(Wheel::Left, factor) => torque.0 *= factor,
+                FaultKind::WheelTorque(Wheel::Right, factor) => torque.1 *= factor,
+                _ => {}
+            }
         }
+        torque
     }
 
-    fn moving(&mut self, direction: &Direction, elapsed: &Duration) {
-        let mut state = self.state.lock().unwrap();
-        let vector = direction_to_vector(state.direction);
-        match direction {
-            Direction::Forward => state.position += vector * elapsed.as_secs_f32() * self.speed,
-            Direction::Backward => state.position -= vector * elapsed.as_secs_f32() * self.speed,
-            Direction::None => {}
+    fn dirt_detected(&self) -> bool {
+        self.sensors
+            .reading(self.dirt_sensor)
+            .copied()
+            .unwrap_or(false)
+    }
+
+    // picks up the dirt under the robot
+    fn clean(&mut self, world: &World, elapsed: &Duration) {
+        let position = self.pose.position;
+        let radius = self.radius;
+
+        let mut dirt = world.dirt.lock().unwrap();
+        self.surface = dirt.surface_at(position);
+        dirt.pickup(position, radius.mm(), elapsed);
+    }
+
+    // the wheels slip depending on the surface and the robot can't move into walls
+    // the whole path of a step is checked, a long step stops at the wall instead of passing it
+    // it drives over anything below its clearance and under anything above its height
+    // a weak wheel motor slows the robot down and pulls it to that side
+    // slip = share of the commanded motion that got lost, 0.0 = none; 1.0 = standing still
+    // the robot is stuck when the slip stays high
+    fn moving(&mut self, world: &World, direction: &Direction, elapsed: &Duration) {
+        let (left, right) = self.wheel_torque();
+        let speed = match direction {
+            Direction::Forward => self.speed,
+            Direction::Backward => -self.speed,
+            Direction::None => Speed::default(),
+        };
+        let yaw_rate =
+            AngularSpeed::from_radians_per_s(speed.mm_per_s() * (right - left) / WHEEL_BASE.mm());
+        self.pose.rotate(yaw_rate * *elapsed);
+
+        let commanded = self.pose.forward() * (speed * *elapsed).mm();
+        let actual = commanded * self.surface.traction() * ((left + right) / 2.0);
+        let impact = world.geometry().sweep_circle(
+            self.pose.position,
+            actual,
+            self.radius,
+            self.clearance,
+            self.height,
+        );
+        let actual = match impact {
+            Some(t) => {
+                let length = actual.length();
+                let travel = (t * length - CONTACT_GAP.mm()).max(0.0);
+                actual * (travel / length)
+            }
+            None => actual,
+        };
+        self.pose.position += actual;
+
+        let commanded_speed = Length::from_mm(commanded.length()) / *elapsed;
+        let actual_speed = Length::from_mm(actual.length()) / *elapsed;
+        self.slip = if commanded_speed > Speed::default() {
+            1.0 - actual_speed / commanded_speed
+        } else {
+            0.0
+        };
+        if self.slip > 1.0 - STUCK_SPEED_RATIO {
+            self.stuck_time += *elapsed;
+        } else {
+            self.stuck_time = Duration::ZERO;
         }
-        drop(state);
+        self.sensor_stuck = self.stuck_time >= STUCK_TIME;
     }
 
     // + = counter clockwise; - = clockwise
-    fn rotate(&mut self, rotation: f32, elapsed: &Duration) {
-        let mut state = self.state.lock().unwrap();
-        state.direction +=
-            elapsed.as_secs_f32() * rotation.clamp(-self.rotation_speed, self.rotation_speed);
-        drop(state);
+    fn rotate(&mut self, rotation: AngularSpeed, elapsed: &Duration) {
+        let (left, right) = self.wheel_torque();
+        self.pose
+            .rotate(rotation.clamp(self.rotation_speed) * ((left + right) / 2.0) * *elapsed);
+    }
+
+    // rotates towards an absolute direction, returns true once it is reached
+    // the direction comes from the gyro, so a drifting gyro turns the robot wrong
+    fn rotate_to(&mut self, target: Angle, elapsed: &Duration) -> bool {
+        if self.heading.approx_eq(target, HEADING_TOLERANCE) {
+            return true;
+        }
+        self.rotate((target - self.heading) / *elapsed, elapsed);
+        false
     }
 
-    fn goto_nearest_wall(&mut self, room: &Arc<Vec<Line>>, quit: Arc<AtomicBool>) {
+    fn goto_nearest_wall(&mut self, world: &World, quit: &Arc<AtomicBool>) {
         // rotate to nearest wall
-        run_with_interval(self.interval, &quit, |elapsed| {
-            let mut min_dist = f32::MAX;
-            let mut min_dist_dir = usize::MAX;
-            self.scan_lidar(room);
-
-            let state = self.state.lock().unwrap();
-            state.lidar.iter().enumerate().for_each(|(num, dist)| {
-                if *dist < min_dist {
-                    min_dist = *dist;
-                    min_dist_dir = num;
-                }
-            });
-            drop(state);
+        // the scan is older than the current direction,
+        // so the target is kept as an absolute direction
+        let mut target = None;
+        run_with_interval(self.interval, quit, |elapsed| {
+            self.sense(world);
+            self.clean(world, &elapsed);
+            if let Some(scan) = self.new_scan() {
+                target = scan
+                    .closest()
+                    .map(|(num, _)| self.scan_direction(scan, num));
+            }
 
-            // 0.0 = robot forward direction
-            if min_dist_dir == 0 {
-                return true; // stop looping
+            match target {
+                Some(target) => self.rotate_to(target, &elapsed),
+                None => self.lidar_timed_out(),
             }
-            if min_dist_dir <= 180 {
-                self.rotate(self.rotation_speed, &elapsed);
-            } else {
-                self.rotate(-self.rotation_speed, &elapsed);
+        });
+
+        // without a lidar it drives straight until it hits something
+        let mut min_dist = Length::INFINITY;
+        run_with_interval(self.interval, quit, |elapsed| {
+            self.sense(world);
+            self.clean(world, &elapsed);
+            if let Some(scan) = self.new_scan() {
+                min_dist = scan.closest().map_or(Length::INFINITY, |(num, dist)| {
+                    dist - self.travelled_since(scan.timestamps[num])
+                });
+            }
+
+            if min_dist <= self.radius + Length::from_mm(10.0)
+                || self.sensor_stuck
+                || self.bumper_pressed()
+                || self.obstacle_ahead()
+            {
+                return true;
             }
 
-            false // continue looping
+            // the distance shrinks between scans while driving towards the wall
+            let position = self.pose.position;
+            self.moving(world, &Direction::Forward, &elapsed);
+            min_dist -= Length::from_mm(position.distance(self.pose.position));
+            false
         });
+    }
 
-        run_with_interval(self.interval, &quit, |elapsed| {
-            self.scan_lidar(room);
+    // rotate 90deg to wall
+    fn align_to_wall(&mut self, world: &World, quit: &Arc<AtomicBool>) {
+        let mut target = None;
+        run_with_interval(self.interval, quit, |elapsed| {
+            self.sense(world);
+            self.clean(world, &elapsed);
+            if let Some(scan) = self.new_scan() {
+                // 270 = right side
+                target = scan
+                    .closest()
+                    .map(|(num, _)| self.scan_direction(scan, num) - Angle::from_degrees(270.0));
+            }
 
-            let mut min_dist = f32::MAX;
-            let state = self.state.lock().unwrap();
-            state.lidar.iter().for_each(|x| {
-                if *x < min_dist {
-                    min_dist = *x;
-                }
-            });
+            match target {
+                Some(target) => self.rotate_to(target, &elapsed),
+                None => self.lidar_timed_out(),
+            }
+        });
+    }
+
+    // follows the wall until the dirt sensor fires or the robot gets stuck
+    fn follow_wall(&mut self, world: &World, quit: &Arc<AtomicBool>) {
+        let mut last_error = 0.0;
+        let mut integral = 0.0;
+        let mut move_dir = Direction::Forward;
+        run_with_interval(self.interval, quit, |elapsed| {
+            self.sense(world);
+            self.clean(world, &elapsed);
+
+            if self.dirt_detected() || self.sensor_stuck {
+                return true;
+            }
+
+            let wall_sensor = self.sensors.sensor(self.wall_sensor);
+            let wall_dist = self
+                .sensors
+                .reading(self.wall_sensor)
+                .and_then(|reading| wall_sensor.distance(*reading));
+            if wall_dist.is_none() {
+                move_dir = Direction::None;
+            } else {
+                move_dir = Direction::Forward;
+            }
+
+            let error = (wall_dist.unwrap_or(wall_sensor.max_range) - WALL_SETPOINT).mm();
+            let p = error;
+            integral += error;
+            let i = integral;
+            let d = error - last_error;
+
+            // TODO: tweak p i and d values
+            let correction = p * 0.5 + i * 0.001 + d * 20.0;
 
-            if min_dist <= state.radius + 10.0 {
+            self.rotate(AngularSpeed::from_degrees_per_s(-correction), &elapsed);
+            self.moving(world, &move_dir, &elapsed);
+
+            // the true gap comes from the world, the sensor only sees along its beam
+            if wall_dist.is_some() {
+                let gap =
+                    Length::from_mm(world.geometry().distance_at(self.pose.position)) - self.radius;
+                self.wall_error_sum += (gap - WALL_GAP).mm().powi(2);
+                self.wall_error_ticks += 1;
+            }
+
+            last_error = error;
+
+            false
+        });
+    }
+
+    // drives an outward spiral around the dirty spot
+    fn spot_clean(&mut self, world: &World, quit: &Arc<AtomicBool>) {
+        let mut time = Duration::ZERO;
+        run_with_interval(self.interval, quit, |elapsed| {
+            self.sense(world);
+            self.clean(world, &elapsed);
+
+            time += elapsed;
+            if time >= SPOT_CLEAN_TIME
+                || self.bumper_pressed()
+                || self.obstacle_ahead()
+                || self.sensor_stuck
+            {
                 return true;
             }
-            drop(state);
 
-            self.moving(&Direction::Forward, &elapsed);
+            // slowing down the rotation widens the spiral
+            let rotation = self.rotation_speed * (1.0 - time.div_duration_f32(SPOT_CLEAN_TIME));
+            self.rotate(rotation, &elapsed);
+            self.moving(world, &Direction::Forward, &elapsed);
+            false
+        });
+    }
+
+    // back off and turn away instead of spinning the wheels forever
+    fn escape(&mut self, world: &World, quit: &Arc<AtomicBool>) {
+        let mut time = Duration::ZERO;
+        run_with_interval(self.interval, quit, |elapsed| {
+            self.sense(world);
+            time += elapsed;
+            if time < ESCAPE_BACKUP_TIME {
+                self.moving(world, &Direction::Backward, &elapsed);
+            } else if time < ESCAPE_BACKUP_TIME + ESCAPE_TURN_TIME {
+                self.rotate(self.rotation_speed, &elapsed);
+            } else {
+                return true;
+            }
             false
         });
+        self.stuck_time = Duration::ZERO;
+        self.sensor_stuck = false;
     }
 
-    pub fn run(self, room: Arc<Vec<Line>>, quit: Arc<AtomicBool>) -> JoinHandle<()> {
+    pub fn run(self, world: Arc<World>, quit: Arc<AtomicBool>) -> JoinHandle<()> {
         thread::spawn(move || {
             let mut robot = self;
 
-            robot.goto_nearest_wall(&room, Arc::clone(&quit));
+            while !quit.load(Ordering::Relaxed) {
+                robot.goto_nearest_wall(&world, &quit);
+                robot.align_to_wall(&world, &quit);
+                robot.follow_wall(&world, &quit);
 
-            // rotate 90deg to wall
-            run_with_interval(robot.interval, &quit, |elapsed| {
-                robot.scan_lidar(&room);
+                if robot.sensor_stuck {
+                    robot.escape(&world, &quit);
+                } else if robot.dirt_detected() {
+                    robot.spot_clean(&world, &quit);
+                }
+            }
+        })
+    }
+}
 
-                let mut min_dir = 0;
-                let mut min_val = LIDAR_MAX_DIST;
-                let state = robot.state.lock().unwrap();
-                state.lidar.iter().enumerate().for_each(|(num, x)| {
-                    if *x < min_val {
-                        min_dir = num;
-                        min_val = *x;
-                    }
-                });
-                drop(state);
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fault::Fault;
+    use crate::line::Line;
+
+    fn robot() -> Robot {
+        Robot::new(
+            Length::from_mm(1000.0),
+            Length::from_mm(1000.0),
+            SensorSuite::Lidar,
+        )
+    }
 
-                // 270 = right side
-                if min_dir == 270 {
-                    return true;
-                }
+    // 2 x 2 m around the robot
+    fn room() -> World {
+        let corner = |x, y| Float2::new(x, y);
+        World::from_walls(vec![
+            Line::new(corner(0.0, 0.0), corner(2000.0, 0.0)),
+            Line::new(corner(2000.0, 0.0), corner(2000.0, 2000.0)),
+            Line::new(corner(2000.0, 2000.0), corner(0.0, 2000.0)),
+            Line::new(corner(0.0, 2000.0), corner(0.0, 0.0)),
+        ])
+    }
 
-                robot.rotate(robot.rotation_speed, &elapsed);
-                false
-            });
-
-            // follow wall
-            let mut last_error = 0.0;
-            let mut integral = 0.0;
-            let mut move_dir = Direction::Forward;
-            run_with_interval(robot.interval, &quit, |elapsed| {
-                robot.scan_lidar(&room);
-                robot.check_collision(&room);
-                robot.check_wall(&room);
-
-                if robot.sensor_wall == WALL_MAX_DIST {
-                    move_dir = Direction::None;
-                } else {
-                    move_dir = Direction::Forward;
-                }
+    fn scenario(kind: FaultKind, start: Duration, duration: Duration) -> Scenario {
+        let mut scenario = Scenario::new();
+        scenario.add(Fault::new(kind, start, duration));
+        scenario
+    }
 
-                let error = robot.sensor_wall - 21.5; // 21.5 ~ 10mm to the wall
-                let p = error;
-                integral += error;
-                let i = integral;
-                let d = error - last_error;
+    // senses every 10 ms while sliding 1 mm per tick to the east
+    // returns the scans that arrived
+    fn scans(robot: &mut Robot, world: &World, ticks: u64) -> Vec<Scan> {
+        let mut scans = Vec::new();
+        for tick in 0..ticks {
+            robot.pose.position += Float2::new(1.0, 0.0);
+            robot.sense_at(world, Duration::from_millis(tick * 10));
+            if let Some(scan) = robot.new_scan() {
+                assert_eq!(*scan, *robot.scan);
+                scans.push(scan.clone());
+            }
+        }
+        scans
+    }
 
-                // TODO: tweak p i and d values
-                let correction = p * 0.5 + i * 0.001 + d * 20.0;
+    // counts its updates, like a sensor added from outside of the robot
+    struct Ticks(u32);
 
-                robot.rotate(-correction, &elapsed);
-                robot.moving(&move_dir, &elapsed);
+    impl Sensor for Ticks {
+        type Reading = u32;
 
-                last_error = error;
+        fn mount(&self) -> Mount {
+            Mount::center()
+        }
 
-                false
-            });
-        })
+        fn rate(&self) -> f32 {
+            50.0
+        }
+
+        fn update(&mut self, _world: &World, _pose: Pose, _time: Duration) -> Option<u32> {
+            self.0 += 1;
+            Some(self.0)
+        }
+    }
+
+    #[test]
+    fn custom_sensors_deliver_readings() {
+        let world = World::from_walls(Vec::new());
+        let mut robot = Robot::new(
+            Length::from_mm(1000.0),
+            Length::from_mm(1000.0),
+            SensorSuite::Lidar,
+        );
+        let ticks = robot.add_sensor("ticks", Ticks(0));
+        let ms = Duration::from_millis;
+        robot.set_latency(ticks, Latency::new(ms(15), Duration::ZERO));
+        assert!(robot.frame_names.iter().any(|(_, name)| name == "ticks"));
+
+        // captured at 0 and 20, delivered 15 later
+        let mut delivered = Vec::new();
+        for tick in 0..5 {
+            robot.sense_at(&world, ms(tick * 10));
+            delivered.push((
+                robot.reading(ticks).copied(),
+                robot.stamp(ticks),
+                robot.is_fresh(ticks),
+            ));
+        }
+        assert_eq!(
+            delivered,
+            [
+                (None, Duration::ZERO, false),
+                (None, Duration::ZERO, false),
+                (Some(1), Duration::ZERO, true),
+                (Some(1), Duration::ZERO, false),
+                (Some(2), ms(20), true),
+            ]
+        );
+    }
+
+    #[test]
+    fn stopped_lidars_deliver_no_scans() {
+        let world = room();
+        assert!(scans(&mut robot(), &world, 50).len() >= 3);
+
+        let mut stopped = robot();
+        let second = Duration::from_secs(1);
+        stopped.set_scenario(scenario(FaultKind::LidarStopped, Duration::ZERO, second));
+        assert!(scans(&mut stopped, &world, 50).is_empty());
+    }
+
+    #[test]
+    fn frozen_scans_repeat_the_first_one() {
+        let world = room();
+        let moving = scans(&mut robot(), &world, 50);
+        assert!(moving.windows(2).all(|pair| pair[0] != pair[1]));
+
+        let mut frozen = robot();
+        let second = Duration::from_secs(1);
+        frozen.set_scenario(scenario(FaultKind::FrozenScan, Duration::ZERO, second));
+        let scans = scans(&mut frozen, &world, 50);
+        assert!(scans.len() >= 3);
+        assert!(scans.iter().all(|scan| *scan == scans[0]));
+    }
+
+    #[test]
+    fn stuck_bumpers_ignore_the_contact() {
+        // nothing to touch
+        let world = World::from_walls(Vec::new());
+        let mut robot = robot();
+        let ms = Duration::from_millis;
+        robot.set_scenario(scenario(FaultKind::BumperStuck(true), ms(20), ms(50)));
+
+        let pressed: Vec<_> = [0, 10, 20, 60, 70, 80]
+            .into_iter()
+            .map(|time| {
+                robot.sense_at(&world, ms(time));
+                robot.bumper_pressed()
+            })
+            .collect();
+        assert_eq!(pressed, [false, false, true, true, false, false]);
+    }
+
+    #[test]
+    fn weak_wheels_slow_down_and_pull_aside() {
+        let world = World::from_walls(Vec::new());
+        let drive = |robot: &mut Robot| {
+            let interval = robot.interval;
+            for tick in 0..100 {
+                robot.sense_at(&world, interval * tick);
+                robot.moving(&world, &Direction::Forward, &interval);
+            }
+            robot.pose
+        };
+        let healthy = drive(&mut robot());
+        let mut weak = robot();
+        weak.set_scenario(scenario(
+            FaultKind::WheelTorque(Wheel::Left, 0.5),
+            Duration::ZERO,
+            Duration::from_secs(10),
+        ));
+        let pulled = drive(&mut weak);
+
+        let start = Float2::new(1000.0, 1000.0);
+        let (healthy_way, pulled_way) = (
+            healthy.position.distance(start),
+            pulled.position.distance(start),
+        );
+        assert!((healthy_way - 400.0).abs() < 1.0, "{healthy_way}");
+        assert!(
+            (pulled_way / healthy_way - 0.75).abs() < 0.05,
+            "{pulled_way}"
+        );
+        // the right wheel is faster, the robot turns left
+        assert!(healthy.heading.approx_eq(Angle::ZERO, HEADING_TOLERANCE));
+        assert!(pulled.heading.degrees() > 10.0);
+    }
+
+    #[test]
+    fn gets_stuck_driving_into_a_wall() {
+        let world = room();
+        let mut robot = robot();
+        let interval = robot.interval;
+        // 825 mm to the wall at 4 mm per tick
+        for _ in 0..200 {
+            robot.moving(&world, &Direction::Forward, &interval);
+        }
+        assert_eq!(robot.slip, 0.0);
+        assert!(!robot.sensor_stuck);
+
+        // pushing against the wall, stuck after a second
+        for _ in 0..90 {
+            robot.moving(&world, &Direction::Forward, &interval);
+        }
+        assert!(robot.slip > 1.0 - STUCK_SPEED_RATIO);
+        assert!(!robot.sensor_stuck);
+        for _ in 0..30 {
+            robot.moving(&world, &Direction::Forward, &interval);
+        }
+        assert!(robot.sensor_stuck);
+
+        // backing off clears it
+        robot.moving(&world, &Direction::Backward, &interval);
+        assert_eq!(robot.slip, 0.0);
+        assert!(!robot.sensor_stuck);
+    }
+
+    #[test]
+    fn wheels_slip_on_rugs() {
+        let world = room();
+        let mut robot = robot();
+        robot.surface = Surface::Rug;
+        let interval = robot.interval;
+        for _ in 0..150 {
+            robot.moving(&world, &Direction::Forward, &interval);
+            assert!((robot.slip - (1.0 - Surface::Rug.traction())).abs() < 1e-3);
+        }
+        assert!(!robot.sensor_stuck);
+    }
+
+    #[test]
+    fn depth_camera_sees_low_obstacles() {
+        // a cable 40mm in front of the body, below the lidar plane
+        let world = World::from_walls(vec![Line::with_height(
+            Float2::new(1215.0, 900.0),
+            Float2::new(1215.0, 1100.0),
+            Length::ZERO,
+            Length::from_mm(20.0),
+        )]);
+        let mut with_camera = Robot::new(
+            Length::from_mm(1000.0),
+            Length::from_mm(1000.0),
+            SensorSuite::LidarAndDepthCamera,
+        );
+        let mut without = Robot::new(
+            Length::from_mm(1000.0),
+            Length::from_mm(1000.0),
+            SensorSuite::Lidar,
+        );
+        with_camera.sense_at(&world, Duration::ZERO);
+        without.sense_at(&world, Duration::ZERO);
+        assert!(with_camera.obstacle_ahead());
+        assert!(!without.obstacle_ahead());
+    }
+
+    #[test]
+    fn bumper_fires_on_low_walls() {
+        // below the lidar, the robot stops at it and the bumper touches it
+        let world = World::from_walls(vec![Line::with_height(
+            Float2::new(1300.0, 500.0),
+            Float2::new(1300.0, 1500.0),
+            Length::ZERO,
+            Length::from_mm(40.0),
+        )]);
+        let mut robot = Robot::new(
+            Length::from_mm(1000.0),
+            Length::from_mm(1000.0),
+            SensorSuite::Lidar,
+        );
+        let interval = robot.interval;
+        for _ in 0..50 {
+            robot.moving(&world, &Direction::Forward, &interval);
+        }
+        assert!((robot.pose.position.get_x() - (1300.0 - 175.0)).abs() <= CONTACT_GAP.mm());
+
+        // the reading arrives after the bumper latency
+        robot.sense_at(&world, Duration::ZERO);
+        assert!(!robot.bumper_pressed());
+        robot.sense_at(&world, Duration::from_millis(10));
+        assert!(robot.bumper_pressed());
     }
 }
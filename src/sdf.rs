@@ -0,0 +1,195 @@
+use crate::float2::Float2;
+use crate::line::{bounds, Line};
+
+// distance to the closest wall sampled at the corners of square cells
+// walls have no thickness, so nothing is inside of them and the field is never negative,
+// the sign comes in with a body: distance - radius < 0 = the body overlaps a wall
+// samples stop at band, further away only "at least band" is known,
+// so adding or moving a wall only touches the nodes within band of it
+// between the samples the value is interpolated, off by at most one cell diagonal,
+// close to the walls the world refines it with the exact distance
+// cell_size, margin, band = mm
+#[derive(Debug, Clone)]
+pub struct DistanceField {
+    origin: Float2,
+    cell_size: f32,
+    band: f32,
+    width: usize,
+    height: usize,
+    samples: Vec<f32>,
+}
+
+impl DistanceField {
+    // covers the bounding box of the walls grown by the margin
+    pub fn new(walls: &[Line], cell_size: f32, margin: f32, band: f32) -> Self {
+        let (min, max) = bounds(walls);
+        let min = min - Float2::new(margin, margin);
+        let max = max + Float2::new(margin, margin);
+
+        let width = ((max.get_x() - min.get_x()) / cell_size).ceil() as usize + 1;
+        let height = ((max.get_y() - min.get_y()) / cell_size).ceil() as usize + 1;
+        let mut field = Self {
+            origin: min,
+            cell_size,
+            band,
+            width,
+            height,
+            samples: vec![band; width * height],
+        };
+        walls.iter().for_each(|wall| field.insert(wall));
+        field
+    }
+
+    // a new wall can only bring the walls closer
+    pub fn insert(&mut self, wall: &Line) {
+        for (x, y) in self.nodes_near(wall) {
+            let node = self.node(x, y);
+            let sample = &mut self.samples[y * self.width + x];
+            *sample = sample.min(wall.distance_to(node));
+        }
+    }
+
+    // moving or removing a wall can only raise the samples it was the closest wall of,
+    // those get the distance to the remaining walls
+    // remaining(node) = distance to the closest remaining wall, it may stop at band
+    pub fn remove<F>(&mut self, wall: &Line, remaining: F)
+    where
+        F: Fn(Float2) -> f32,
+    {
+        for (x, y) in self.nodes_near(wall) {
+            let node = self.node(x, y);
+            let sample = &mut self.samples[y * self.width + x];
+            if wall.distance_to(node) <= *sample {
+                *sample = remaining(node).min(self.band);
+            }
+        }
+    }
+
+    pub fn get_band(&self) -> f32 {
+        self.band
+    }
+
+    // how far an interpolated sample may be off from the real distance
+    pub fn max_error(&self) -> f32 {
+        self.cell_size * std::f32::consts::SQRT_2
+    }
+
+    fn node(&self, x: usize, y: usize) -> Float2 {
+        self.origin + Float2::new(x as f32, y as f32) * self.cell_size
+    }
+
+    // nodes that can be closer than band to the wall
+    fn nodes_near(&self, wall: &Line) -> impl Iterator<Item = (usize, usize)> {
+        let (a, b) = (wall.get_a(), wall.get_b());
+        let index = |min: f32, max: f32, origin: f32, len: usize| {
+            let first = ((min - self.band - origin) / self.cell_size)
+                .floor()
+                .max(0.0) as usize;
+            let last = ((max + self.band - origin) / self.cell_size).ceil();
+            (first, (last.max(-1.0) as isize).min(len as isize - 1))
+        };
+        let (x0, x1) = index(
+            a.get_x().min(b.get_x()),
+            a.get_x().max(b.get_x()),
+            self.origin.get_x(),
+            self.width,
+        );
+        let (y0, y1) = index(
+            a.get_y().min(b.get_y()),
+            a.get_y().max(b.get_y()),
+            self.origin.get_y(),
+            self.height,
+        );
+        (y0 as isize..=y1)
+            .flat_map(move |y| (x0 as isize..=x1).map(move |x| (x as usize, y as usize)))
+    }
+
+    // samples at the corners of the cell around the point as
+    // [bottom left, bottom right, top left, top right] and the position within it 0.0..=1.0,
+    // None outside the field
+    fn cell(&self, point: Float2) -> Option<([f32; 4], f32, f32)> {
+        let local = (point - self.origin) / self.cell_size;
+        let (x, y) = (local.get_x(), local.get_y());
+        if x < 0.0 || y < 0.0 || x > (self.width - 1) as f32 || y > (self.height - 1) as f32 {
+            return None;
+        }
+        let x0 = (x.floor() as usize).min(self.width.saturating_sub(2));
+        let y0 = (y.floor() as usize).min(self.height.saturating_sub(2));
+        let x1 = (x0 + 1).min(self.width - 1);
+        let y1 = (y0 + 1).min(self.height - 1);
+
+        let at = |x: usize, y: usize| self.samples[y * self.width + x];
+        let corners = [at(x0, y0), at(x1, y0), at(x0, y1), at(x1, y1)];
+        Some((corners, x - x0 as f32, y - y0 as f32))
+    }
+
+    // bilinear between the four surrounding samples, None outside the field
+    pub fn sample(&self, point: Float2) -> Option<f32> {
+        let ([bottom_left, bottom_right, top_left, top_right], tx, ty) = self.cell(point)?;
+        let bottom = bottom_left + (bottom_right - bottom_left) * tx;
+        let top = top_left + (top_right - top_left) * tx;
+        Some(bottom + (top - bottom) * ty)
+    }
+
+    // derivative of the bilinear sample, points away from the walls
+    // about unit length, shorter where the closest wall changes within the cell
+    pub fn gradient(&self, point: Float2) -> Option<Float2> {
+        let ([bottom_left, bottom_right, top_left, top_right], tx, ty) = self.cell(point)?;
+        let dx = (bottom_right - bottom_left) * (1.0 - ty) + (top_right - top_left) * ty;
+        let dy = (top_left - bottom_left) * (1.0 - tx) + (top_right - bottom_right) * tx;
+        Some(Float2::new(dx, dy) / self.cell_size)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn walls() -> Vec<Line> {
+        vec![
+            Line::new(Float2::new(0.0, 0.0), Float2::new(3000.0, 0.0)),
+            Line::new(Float2::new(3000.0, 0.0), Float2::new(3000.0, 2000.0)),
+            Line::new(Float2::new(800.0, 600.0), Float2::new(1700.0, 1500.0)),
+            Line::new(Float2::new(2200.0, 1000.0), Float2::new(2200.0, 1000.0)),
+        ]
+    }
+
+    // points on a grid that doesn't line up with the nodes
+    fn points() -> impl Iterator<Item = Float2> {
+        (0..40).flat_map(|y| {
+            (0..50).map(move |x| Float2::new(x as f32 * 73.0 - 400.0, y as f32 * 61.0 - 300.0))
+        })
+    }
+
+    #[test]
+    fn samples_stay_within_the_error_of_the_distance() {
+        let walls = walls();
+        let field = DistanceField::new(&walls, 50.0, 500.0, 300.0);
+        for point in points() {
+            let exact = walls
+                .iter()
+                .map(|wall| wall.distance_to(point))
+                .fold(field.get_band(), f32::min);
+            let sample = field.sample(point).unwrap();
+            assert!((sample - exact).abs() <= field.max_error(), "{point:?}");
+        }
+    }
+
+    #[test]
+    fn moved_walls_match_a_new_field() {
+        let mut walls = walls();
+        let mut field = DistanceField::new(&walls, 50.0, 500.0, 300.0);
+        let old = walls[2];
+        walls[2] = Line::new(Float2::new(800.0, 600.0), Float2::new(1700.0, 600.0));
+        field.remove(&old, |node| {
+            walls
+                .iter()
+                .map(|wall| wall.distance_to(node))
+                .fold(f32::INFINITY, f32::min)
+        });
+        field.insert(&walls[2]);
+
+        let rebuilt = DistanceField::new(&walls, 50.0, 500.0, 300.0);
+        assert_eq!(field.samples, rebuilt.samples);
+    }
+}
@@ -0,0 +1,390 @@
+use crate::angle::Angle;
+use crate::float2::Float2;
+use crate::pose::Pose;
+use crate::rng::Rng;
+use crate::units::Length;
+use crate::world::World;
+
+use std::any::Any;
+use std::marker::PhantomData;
+use std::time::Duration;
+
+// position = mm relative to the robot center, x = forward, y = left
+// height = above the floor
+// angle = relative to the robot direction
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Mount {
+    pub position: Float2,
+    pub height: Length,
+    pub angle: Angle,
+}
+
+impl Mount {
+    pub fn new(position: Float2, angle: Angle) -> Self {
+        Self {
+            position,
+            height: Length::ZERO,
+            angle,
+        }
+    }
+
+    pub fn with_height(mut self, height: Length) -> Self {
+        self.height = height;
+        self
+    }
+
+    pub fn center() -> Self {
+        Self::new(Float2::ZERO, Angle::ZERO)
+    }
+
+    // pose of the sensor frame in the robot frame
+    pub fn pose(&self) -> Pose {
+        Pose::new(self.position, self.angle)
+    }
+
+    // world pose of the sensor for the given robot pose
+    pub fn transform(&self, pose: Pose) -> Pose {
+        pose.compose(self.pose())
+    }
+}
+
+// time from capturing a reading until it is delivered, like on the robot bus
+// the jitter is added uniformly, so readings can overtake each other
+#[derive(Debug, Copy, Clone, PartialEq, Default)]
+pub struct Latency {
+    pub delay: Duration,
+    pub jitter: Duration,
+}
+
+impl Latency {
+    pub fn new(delay: Duration, jitter: Duration) -> Self {
+        Self { delay, jitter }
+    }
+
+    fn sample(&self, rng: &mut Rng) -> Duration {
+        self.delay + self.jitter.mul_f32(rng.next_f32())
+    }
+}
+
+// sensors get updated with their own pose in the world, the registry applies the mount
+// time = time since the robot started
+pub trait Sensor: Send {
+    type Reading: Send + 'static;
+
+    fn mount(&self) -> Mount;
+
+    // Hz, f32::INFINITY = every control tick
+    // 0.0, negative or NaN = disabled, the sensor isn't updated
+    fn rate(&self) -> f32;
+
+    // returns None while the sensor has no new reading
+    fn update(&mut self, world: &World, pose: Pose, time: Duration) -> Option<Self::Reading>;
+}
+
+// object safe version of Sensor, the readings get boxed
+trait AnySensor: Send {
+    fn mount(&self) -> Mount;
+    fn rate(&self) -> f32;
+    fn update_any(
+        &mut self,
+        world: &World,
+        pose: Pose,
+        time: Duration,
+    ) -> Option<Box<dyn Any + Send>>;
+    fn as_any(&self) -> &dyn Any;
+    fn as_any_mut(&mut self) -> &mut dyn Any;
+}
+
+impl<S: Sensor + 'static> AnySensor for S {
+    fn mount(&self) -> Mount {
+        Sensor::mount(self)
+    }
+
+    fn rate(&self) -> f32 {
+        Sensor::rate(self)
+    }
+
+    fn update_any(
+        &mut self,
+        world: &World,
+        pose: Pose,
+        time: Duration,
+    ) -> Option<Box<dyn Any + Send>> {
+        self.update(world, pose, time)
+            .map(|reading| Box::new(reading) as Box<dyn Any + Send>)
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+}
+
+// typed handle into the registry
+pub struct SensorId<S> {
+    index: usize,
+    sensor: PhantomData<fn() -> S>,
+}
+
+impl<S> Clone for SensorId<S> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<S> Copy for SensorId<S> {}
+
+struct Delivery {
+    arrival: Duration,
+    stamp: Duration,
+    reading: Box<dyn Any + Send>,
+}
+
+// stamp = capture time of the latest delivered reading
+struct Entry {
+    sensor: Box<dyn AnySensor>,
+    latency: Latency,
+    last_update: Option<Duration>,
+    queue: Vec<Delivery>,
+    reading: Option<Box<dyn Any + Send>>,
+    stamp: Duration,
+    fresh: bool,
+}
+
+const JITTER_SEED: u64 = 7;
+
+pub struct SensorRegistry {
+    entries: Vec<Entry>,
+    rng: Rng,
+}
+
+impl Default for SensorRegistry {
+    fn default() -> Self {
+        Self {
+            entries: Vec::new(),
+            rng: Rng::new(JITTER_SEED),
+        }
+    }
+}
+
+impl SensorRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn add<S: Sensor + 'static>(&mut self, sensor: S) -> SensorId<S> {
+        self.entries.push(Entry {
+            sensor: Box::new(sensor),
+            latency: Latency::default(),
+            last_update: None,
+            queue: Vec::new(),
+            reading: None,
+            stamp: Duration::ZERO,
+            fresh: false,
+        });
+        SensorId {
+            index: self.entries.len() - 1,
+            sensor: PhantomData,
+        }
+    }
+
+    pub fn set_latency<S>(&mut self, id: SensorId<S>, latency: Latency) {
+        self.entries[id.index].latency = latency;
+    }
+
+    // updates every sensor that is due at its own rate
+    // and delivers the readings whose latency has passed
+    pub fn update(&mut self, world: &World, pose: Pose, time: Duration) {
+        for entry in self.entries.iter_mut() {
+            entry.fresh = false;
+
+            let rate = entry.sensor.rate();
+            let due = rate > 0.0
+                && entry.last_update.is_none_or(|last_update| {
+                    // a period too long for a Duration never comes round
+                    Duration::try_from_secs_f32(1.0 / rate)
+                        .ok()
+                        .and_then(|period| last_update.checked_add(period))
+                        .is_some_and(|next| time >= next)
+                });
+            if due {
+                entry.last_update = Some(time);
+                let pose = entry.sensor.mount().transform(pose);
+                if let Some(reading) = entry.sensor.update_any(world, pose, time) {
+                    entry.queue.push(Delivery {
+                        arrival: time + entry.latency.sample(&mut self.rng),
+                        stamp: time,
+                        reading,
+                    });
+                }
+            }
+
+            // in order of arrival, not of capture
+            entry.queue.sort_by_key(|delivery| delivery.arrival);
+            let arrived = entry
+                .queue
+                .partition_point(|delivery| delivery.arrival <= time);
+            for delivery in entry.queue.drain(..arrived) {
+                entry.reading = Some(delivery.reading);
+                entry.stamp = delivery.stamp;
+                entry.fresh = true;
+            }
+        }
+    }
+
+    // latest delivered reading, None until the first one arrived
+    // with jitter it can be older than the one before, check the stamp
+    pub fn reading<S: Sensor + 'static>(&self, id: SensorId<S>) -> Option<&S::Reading> {
+        self.entries[id.index]
+            .reading
+            .as_ref()
+            .and_then(|reading| reading.downcast_ref())
+    }
+
+    // true if a reading got delivered in the last update
+    pub fn is_fresh<S>(&self, id: SensorId<S>) -> bool {
+        self.entries[id.index].fresh
+    }
+
+    // capture time of the latest delivered reading
+    pub fn stamp<S>(&self, id: SensorId<S>) -> Duration {
+        self.entries[id.index].stamp
+    }
+
+    pub fn sensor<S: Sensor + 'static>(&self, id: SensorId<S>) -> &S {
+        self.entries[id.index]
+            .sensor
+            .as_any()
+            .downcast_ref()
+            .unwrap()
+    }
+
+    pub fn sensor_mut<S: Sensor + 'static>(&mut self, id: SensorId<S>) -> &mut S {
+        self.entries[id.index]
+            .sensor
+            .as_any_mut()
+            .downcast_mut()
+            .unwrap()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // counts its updates
+    struct Counter {
+        rate: f32,
+        count: u32,
+    }
+
+    impl Sensor for Counter {
+        type Reading = u32;
+
+        fn mount(&self) -> Mount {
+            Mount::center()
+        }
+
+        fn rate(&self) -> f32 {
+            self.rate
+        }
+
+        fn update(&mut self, _world: &World, _pose: Pose, _time: Duration) -> Option<u32> {
+            self.count += 1;
+            Some(self.count)
+        }
+    }
+
+    #[test]
+    fn updates_at_the_sensor_rate() {
+        let world = World::from_walls(Vec::new());
+        let mut registry = SensorRegistry::new();
+        let every_tick = registry.add(Counter {
+            rate: f32::INFINITY,
+            count: 0,
+        });
+        let ten_hz = registry.add(Counter {
+            rate: 10.0,
+            count: 0,
+        });
+        let rare = registry.add(Counter {
+            rate: 1e-20,
+            count: 0,
+        });
+        let disabled: Vec<_> = [0.0, -5.0, f32::NAN]
+            .into_iter()
+            .map(|rate| registry.add(Counter { rate, count: 0 }))
+            .collect();
+
+        for tick in 0..100 {
+            registry.update(&world, Pose::identity(), Duration::from_millis(tick * 10));
+        }
+        assert_eq!(registry.reading(every_tick), Some(&100));
+        assert_eq!(registry.reading(ten_hz), Some(&10));
+        assert_eq!(registry.reading(rare), Some(&1));
+        for id in disabled {
+            assert_eq!(registry.reading(id), None);
+            assert_eq!(registry.sensor(id).count, 0);
+        }
+    }
+
+    #[test]
+    fn readings_arrive_after_their_latency() {
+        let world = World::from_walls(Vec::new());
+        let mut registry = SensorRegistry::new();
+        let id = registry.add(Counter {
+            rate: f32::INFINITY,
+            count: 0,
+        });
+        registry.set_latency(id, Latency::new(Duration::from_millis(25), Duration::ZERO));
+
+        for tick in 0..10 {
+            let time = Duration::from_millis(tick * 10);
+            registry.update(&world, Pose::identity(), time);
+            if tick < 3 {
+                assert_eq!(registry.reading(id), None);
+                assert!(!registry.is_fresh(id));
+            } else {
+                // captured three ticks ago, stamped with the capture time
+                assert_eq!(registry.reading(id), Some(&(tick as u32 - 2)));
+                assert_eq!(registry.stamp(id), time - Duration::from_millis(30));
+                assert!(registry.is_fresh(id));
+            }
+        }
+    }
+
+    #[test]
+    fn jitter_reorders_readings() {
+        let world = World::from_walls(Vec::new());
+        let mut registry = SensorRegistry::new();
+        let id = registry.add(Counter {
+            rate: f32::INFINITY,
+            count: 0,
+        });
+        // more jitter than time between the readings
+        registry.set_latency(id, Latency::new(Duration::ZERO, Duration::from_millis(50)));
+
+        let mut last_stamp = Duration::ZERO;
+        let mut overtaken = 0;
+        for tick in 0..200 {
+            let time = Duration::from_millis(tick * 10);
+            registry.update(&world, Pose::identity(), time);
+            if !registry.is_fresh(id) {
+                continue;
+            }
+            let stamp = registry.stamp(id);
+            assert!(stamp <= time && time - stamp <= Duration::from_millis(50));
+            // the reading still belongs to its stamp
+            let count = registry.reading(id).copied().unwrap();
+            assert_eq!(Duration::from_millis(10) * (count - 1), stamp);
+            if stamp < last_stamp {
+                overtaken += 1;
+            }
+            last_stamp = stamp;
+        }
+        assert!(overtaken > 0);
+    }
+}
@@ -0,0 +1,249 @@
+use crate::angle::Angle;
+
+use std::time::Duration;
+
+// quantities carry their unit in the type, plain f32 only comes out of the named accessors
+// the simulation works in millimetres, other units are converted when the value is created
+// time is std::time::Duration, angles are Angle
+
+// distance or size, stored in mm
+#[derive(Debug, Copy, Clone, PartialEq, PartialOrd, Default)]
+pub struct Length(f32);
+
+// stored in mm/s
+#[derive(Debug, Copy, Clone, PartialEq, PartialOrd, Default)]
+pub struct Speed(f32);
+
+// stored in degree/s, not wrapped, a rate may exceed half a turn
+#[derive(Debug, Copy, Clone, PartialEq, PartialOrd, Default)]
+pub struct AngularSpeed(f32);
+
+impl Length {
+    pub const ZERO: Length = Length(0.0);
+    pub const INFINITY: Length = Length(f32::INFINITY);
+
+    pub const fn from_mm(mm: f32) -> Self {
+        Self(mm)
+    }
+
+    pub const fn from_m(m: f32) -> Self {
+        Self(m * 1000.0)
+    }
+
+    pub fn mm(self) -> f32 {
+        self.0
+    }
+
+    #[allow(dead_code)]
+    pub fn m(self) -> f32 {
+        self.0 / 1000.0
+    }
+
+    pub fn abs(self) -> Self {
+        Self(self.0.abs())
+    }
+
+    pub fn min(self, other: Self) -> Self {
+        Self(self.0.min(other.0))
+    }
+
+    pub fn max(self, other: Self) -> Self {
+        Self(self.0.max(other.0))
+    }
+}
+
+impl Speed {
+    pub const fn from_mm_per_s(mm_per_s: f32) -> Self {
+        Self(mm_per_s)
+    }
+
+    #[allow(dead_code)]
+    pub const fn from_m_per_s(m_per_s: f32) -> Self {
+        Self(m_per_s * 1000.0)
+    }
+
+    pub fn mm_per_s(self) -> f32 {
+        self.0
+    }
+
+    #[allow(dead_code)]
+    pub fn m_per_s(self) -> f32 {
+        self.0 / 1000.0
+    }
+}
+
+impl AngularSpeed {
+    pub const fn from_degrees_per_s(degrees_per_s: f32) -> Self {
+        Self(degrees_per_s)
+    }
+
+    pub fn from_radians_per_s(radians_per_s: f32) -> Self {
+        Self(radians_per_s.to_degrees())
+    }
+
+    #[allow(dead_code)]
+    pub fn degrees_per_s(self) -> f32 {
+        self.0
+    }
+
+    #[allow(dead_code)]
+    pub fn radians_per_s(self) -> f32 {
+        self.0.to_radians()
+    }
+
+    pub fn clamp(self, limit: Self) -> Self {
+        Self(self.0.clamp(-limit.0.abs(), limit.0.abs()))
+    }
+}
+
+macro_rules! impl_linear_ops {
+    ($unit:ident) => {
+        impl std::ops::Add for $unit {
+            type Output = Self;
+
+            fn add(self, other: Self) -> Self {
+                Self(self.0 + other.0)
+            }
+        }
+
+        impl std::ops::Sub for $unit {
+            type Output = Self;
+
+            fn sub(self, other: Self) -> Self {
+                Self(self.0 - other.0)
+            }
+        }
+
+        impl std::ops::Neg for $unit {
+            type Output = Self;
+
+            fn neg(self) -> Self {
+                Self(-self.0)
+            }
+        }
+
+        impl std::ops::Mul<f32> for $unit {
+            type Output = Self;
+
+            fn mul(self, other: f32) -> Self {
+                Self(self.0 * other)
+            }
+        }
+
+        impl std::ops::Div<f32> for $unit {
+            type Output = Self;
+
+            fn div(self, other: f32) -> Self {
+                Self(self.0 / other)
+            }
+        }
+
+        // ratio of two quantities of the same unit
+        impl std::ops::Div for $unit {
+            type Output = f32;
+
+            fn div(self, other: Self) -> f32 {
+                self.0 / other.0
+            }
+        }
+
+        impl std::ops::AddAssign for $unit {
+            fn add_assign(&mut self, rhs: Self) {
+                self.0 += rhs.0;
+            }
+        }
+
+        impl std::ops::SubAssign for $unit {
+            fn sub_assign(&mut self, rhs: Self) {
+                self.0 -= rhs.0;
+            }
+        }
+    };
+}
+
+impl_linear_ops!(Length);
+impl_linear_ops!(Speed);
+impl_linear_ops!(AngularSpeed);
+
+impl std::ops::Mul<Duration> for Speed {
+    type Output = Length;
+
+    fn mul(self, time: Duration) -> Length {
+        Length(self.0 * time.as_secs_f32())
+    }
+}
+
+impl std::ops::Div<Duration> for Length {
+    type Output = Speed;
+
+    fn div(self, time: Duration) -> Speed {
+        Speed(self.0 / time.as_secs_f32())
+    }
+}
+
+impl std::ops::Mul<Duration> for AngularSpeed {
+    type Output = Angle;
+
+    fn mul(self, time: Duration) -> Angle {
+        Angle::from_degrees(self.0 * time.as_secs_f32())
+    }
+}
+
+impl std::ops::Div<Duration> for Angle {
+    type Output = AngularSpeed;
+
+    fn div(self, time: Duration) -> AngularSpeed {
+        AngularSpeed(self.degrees() / time.as_secs_f32())
+    }
+}
+
+impl std::fmt::Display for Length {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{}mm", self.0)
+    }
+}
+
+impl std::fmt::Display for Speed {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{}mm/s", self.0)
+    }
+}
+
+impl std::fmt::Display for AngularSpeed {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{}°/s", self.0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn converts_between_units() {
+        assert_eq!(Length::from_m(1.5).mm(), 1500.0);
+        assert_eq!(Length::from_mm(250.0).m(), 0.25);
+        assert_eq!(Speed::from_m_per_s(0.4), Speed::from_mm_per_s(400.0));
+        assert_eq!(Speed::from_mm_per_s(400.0).m_per_s(), 0.4);
+        let rate = AngularSpeed::from_radians_per_s(std::f32::consts::PI);
+        assert!((rate.degrees_per_s() - 180.0).abs() < 1e-4);
+        assert!((rate.radians_per_s() - std::f32::consts::PI).abs() < 1e-6);
+    }
+
+    #[test]
+    fn rates_convert_over_time() {
+        let half = Duration::from_millis(500);
+        let speed = Speed::from_mm_per_s(300.0);
+        assert_eq!(speed * half, Length::from_mm(150.0));
+        assert_eq!(Length::from_m(0.15) / half, speed);
+
+        let rate = AngularSpeed::from_degrees_per_s(90.0);
+        assert_eq!(rate * half, Angle::from_degrees(45.0));
+        assert_eq!(Angle::from_degrees(45.0) / half, rate);
+        // rates past half a turn aren't wrapped, only the size of the limit counts
+        assert_eq!(
+            AngularSpeed::from_degrees_per_s(400.0).clamp(AngularSpeed::from_degrees_per_s(-300.0)),
+            AngularSpeed::from_degrees_per_s(300.0)
+        );
+    }
+}
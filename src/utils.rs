@@ -1,44 +1,30 @@
-use crate::{float2::Float2, line::Line};
+use crate::{float2::Float2, line::Line, units::Length};
 use std::{
     sync::atomic::{AtomicBool, Ordering},
     thread,
     time::{Duration, Instant},
 };
 
-pub fn direction_to_vector(direction: f32) -> Float2 {
-    let radians = direction.to_radians();
-    Float2::new(radians.cos(), radians.sin())
-}
-
-pub fn intersection_distance(origin: Float2, vector: Float2, line: Line) -> f32 {
-    let origin_x = origin.get_x();
-    let origin_y = origin.get_y();
-    let vector_x = vector.get_x();
-    let vector_y = vector.get_y();
-    let point1_x = line.get_a().get_x();
-    let point1_y = line.get_a().get_y();
-    let point2_x = line.get_b().get_x();
-    let point2_y = line.get_b().get_y();
-
-    // Line direction
-    let dx = point2_x - point1_x;
-    let dy = point2_y - point1_y;
+// distance along the ray to the line, None if the ray misses it
+pub fn intersection_distance(origin: Float2, vector: Float2, line: Line) -> Option<f32> {
+    // line direction
+    let wall = line.get_b() - line.get_a();
+    let to_line = line.get_a() - origin;
 
-    let denom = vector_x * dy - vector_y * dx;
+    let denom = vector.cross(wall);
 
-    /* if denom == 0.0 {
-        println!("Lines are parallel");
-    } */
+    let t = to_line.cross(wall) / denom;
+    let s = to_line.cross(vector) / denom;
 
-    let t = ((point1_x - origin_x) * dy - (point1_y - origin_y) * dx) / denom;
-    let s = ((point1_x - origin_x) * vector_y - (point1_y - origin_y) * vector_x) / denom;
-
-    // max lidar distance 20m
-    let mut distance = 20000.0;
     if t >= 0.0 && (0.0..=1.0).contains(&s) {
-        distance = t;
+        Some(t)
+    } else {
+        None
     }
-    distance
+}
+
+pub fn circle_intersects_line(center: Float2, radius: Length, line: Line) -> bool {
+    line.distance_to(center) <= radius.mm()
 }
 
 pub fn run_with_interval<F>(interval: Duration, quit: &AtomicBool, mut f: F)
@@ -1,14 +1,17 @@
 extern crate sdl3;
 
 use std::sync::atomic::{AtomicBool, Ordering};
-use std::sync::{Arc, Mutex};
+use std::sync::Arc;
 use std::thread;
 use std::time::{Duration, Instant};
 
+use crate::angle::Angle;
 use crate::float2::Float2;
-use crate::line::Line;
-use crate::robot::RobotState;
-use crate::utils::direction_to_vector;
+use crate::frames::{FrameId, WORLD};
+use crate::pose::Pose;
+use crate::robot::StateHandle;
+use crate::units::Length;
+use crate::world::World;
 use sdl3::event::Event;
 use sdl3::keyboard::Keycode;
 use sdl3::pixels::Color;
@@ -16,25 +19,48 @@ use sdl3::render::{Canvas, FPoint};
 use sdl3::video::Window;
 use sdl3::EventPump;
 
-const SCALE: f32 = 10.0;
+const SCALE: Length = Length::from_mm(10.0); // per pixel
 const WIDTH: u32 = 500;
 const HEIGHT: u32 = 500;
 
-fn draw_line_cartesian(x1: f32, y1: f32, x2: f32, y2: f32, canvas: &mut Canvas<Window>) {
+fn to_pixels(length: Length) -> f32 {
+    length / SCALE
+}
+
+// view = pose of the bottom left screen corner in world
+// world y points up, screen y points down
+fn to_screen(view: Pose, point: Float2) -> FPoint {
+    let point = view.inverse_transform_point(point);
+    FPoint::new(
+        to_pixels(Length::from_mm(point.get_x())),
+        HEIGHT as f32 - to_pixels(Length::from_mm(point.get_y())),
+    )
+}
+
+// the frame in the middle of the screen, the world frame keeps its origin bottom left
+fn view_of(frame: FrameId, pose: Pose) -> Pose {
+    if frame == WORLD {
+        return Pose::identity();
+    }
+    let center = Float2::new(
+        (SCALE * WIDTH as f32 / 2.0).mm(),
+        (SCALE * HEIGHT as f32 / 2.0).mm(),
+    );
+    pose.compose(Pose::new(-center, Angle::ZERO))
+}
+
+fn draw_line_cartesian(view: Pose, a: Float2, b: Float2, canvas: &mut Canvas<Window>) {
     canvas
-        .draw_line(
-            FPoint::new(x1 / SCALE, HEIGHT as f32 - y1 / SCALE),
-            FPoint::new(x2 / SCALE, HEIGHT as f32 - y2 / SCALE),
-        )
+        .draw_line(to_screen(view, a), to_screen(view, b))
         .unwrap();
 }
 
-fn draw_circle(render: &mut Canvas<Window>, position: Float2, radius: f32) {
-    let diameter = radius * 2.0 / SCALE;
+fn draw_circle(render: &mut Canvas<Window>, view: Pose, position: Float2, radius: Length) {
+    let diameter = to_pixels(radius) * 2.0;
 
-    let pos_x = position.get_x() / SCALE;
-    let pos_y = HEIGHT as f32 - position.get_y() / SCALE;
-    let mut x = radius / SCALE - 1.0;
+    let center = to_screen(view, position);
+    let (pos_x, pos_y) = (center.x, center.y);
+    let mut x = to_pixels(radius) - 1.0;
     let mut y = 0.0;
     let mut tx = 1.0;
     let mut ty = 1.0;
@@ -85,6 +111,8 @@ pub struct Viewport {
     canvas: Canvas<Window>,
     event_pump: EventPump,
     show_lidar: bool,
+    frame: usize,
+    shown: usize,
 }
 
 impl Viewport {
@@ -104,6 +132,8 @@ impl Viewport {
             canvas,
             event_pump,
             show_lidar: false,
+            frame: 0,
+            shown: 0,
         }
     }
 
@@ -120,18 +150,18 @@ impl Viewport {
                     repeat: false,
                     ..
                 } => self.show_lidar = !self.show_lidar,
+                Event::KeyDown {
+                    keycode: Some(Keycode::F),
+                    repeat: false,
+                    ..
+                } => self.frame += 1,
                 _ => {}
             }
         }
         false
     }
 
-    pub fn draw(
-        &mut self,
-        room: Arc<Vec<Line>>,
-        robot: &Arc<Mutex<RobotState>>,
-        quit: Arc<AtomicBool>,
-    ) {
+    pub fn draw(&mut self, world: Arc<World>, robot: &StateHandle, quit: Arc<AtomicBool>) {
         let mut last_updated = Instant::now();
         let update_interval = Duration::from_millis(16);
 
@@ -149,46 +179,57 @@ impl Viewport {
                 self.canvas.set_draw_color(Color::RGB(0, 0, 0));
                 self.canvas.clear();
 
+                // everything is drawn in the world frame and moved into the shown frame
+                // F cycles through the frames of the robot
+                let robot_state = robot.load();
+                let view = match robot_state.frames.len() {
+                    0 => Pose::identity(),
+                    len => {
+                        let (id, pose) = robot_state.frames[self.frame % len];
+                        if self.frame % len != self.shown {
+                            self.shown = self.frame % len;
+                            let title = format!("Pathfinder - {}", robot_state.frame_name(id));
+                            self.canvas.window_mut().set_title(&title).unwrap();
+                        }
+                        view_of(id, pose)
+                    }
+                };
+
                 // Draw walls
                 self.canvas.set_draw_color(Color::RGB(255, 0, 0));
-                room.iter().for_each(|wall| {
-                    draw_line_cartesian(
-                        wall.get_a().get_x(),
-                        wall.get_a().get_y(),
-                        wall.get_b().get_x(),
-                        wall.get_b().get_y(),
-                        &mut self.canvas,
-                    );
+                world.geometry().walls().iter().for_each(|wall| {
+                    draw_line_cartesian(view, wall.get_a(), wall.get_b(), &mut self.canvas);
                 });
 
                 // Draw robot
-                let robot_state = robot.lock().unwrap();
-                draw_circle(&mut self.canvas, robot_state.position, robot_state.radius);
-                let vector = direction_to_vector(robot_state.direction);
-                let line_end = vector * robot_state.radius + robot_state.position;
-                draw_line_cartesian(
-                    robot_state.position.get_x(),
-                    robot_state.position.get_y(),
-                    line_end.get_x(),
-                    line_end.get_y(),
-                    &mut self.canvas,
-                );
+                let pose = robot_state.pose;
+                draw_circle(&mut self.canvas, view, pose.position, robot_state.radius);
+                let line_end = pose.transform_point(Float2::new(robot_state.radius.mm(), 0.0));
+                // red = rolling freely, yellow = the wheels spin without moving the robot
+                let slip = (robot_state.slip.clamp(0.0, 1.0) * 255.0) as u8;
+                self.canvas.set_draw_color(Color::RGB(255, slip, 0));
+                draw_line_cartesian(view, pose.position, line_end, &mut self.canvas);
 
                 // Draw Lidar
+                // the scan is in the lidar frame
                 if self.show_lidar {
+                    let lidar = robot_state
+                        .frame_pose(robot_state.lidar_frame)
+                        .unwrap_or(pose);
                     self.canvas.set_draw_color(Color::RGB(0, 255, 0));
                     robot_state
                         .lidar
+                        .ranges
                         .iter()
                         .enumerate()
+                        .filter_map(|(num, range)| range.map(|distance| (num, distance)))
                         .for_each(|(num, distance)| {
-                            let vector = direction_to_vector(num as f32 + robot_state.direction);
-                            let colision_point = vector * *distance + robot_state.position;
+                            let vector = robot_state.lidar.angle(num).to_vector();
+                            let colision_point = lidar.transform_point(vector * distance.mm());
                             draw_line_cartesian(
-                                robot_state.position.get_x(),
-                                robot_state.position.get_y(),
-                                colision_point.get_x(),
-                                colision_point.get_y(),
+                                view,
+                                lidar.position,
+                                colision_point,
                                 &mut self.canvas,
                             );
                         });
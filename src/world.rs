@@ -0,0 +1,388 @@
+use crate::dirt::DirtMap;
+use crate::float2::Float2;
+use crate::grid::Grid;
+use crate::line::Line;
+use crate::polygon::Polygon;
+use crate::raycast::WallArrays;
+use crate::sdf::DistanceField;
+use crate::units::Length;
+use crate::utils::circle_intersects_line;
+
+use std::sync::{Mutex, RwLock, RwLockReadGuard};
+
+const GRID_CELL_SIZE: f32 = 250.0; // mm
+const FIELD_CELL_SIZE: f32 = 50.0; // mm
+const FIELD_MARGIN: f32 = 500.0; // mm around the walls
+const FIELD_BAND: f32 = 500.0; // mm, beyond the field only knows the walls are further
+
+// the robot thread and the viewer share the world, walls can move while they run
+// readers lock the geometry only for a query, so a moving door waits at most for one
+// the dirt gets picked up while the robot drives
+pub struct World {
+    geometry: RwLock<Geometry>,
+    pub dirt: Mutex<DirtMap>,
+}
+
+impl World {
+    pub fn new(walls: Vec<Line>, dirt: DirtMap) -> Self {
+        Self {
+            geometry: RwLock::new(Geometry::new(walls)),
+            dirt: Mutex::new(dirt),
+        }
+    }
+
+    // a clean floor, e.g. for testing the sensors
+    #[cfg(test)]
+    pub fn from_walls(walls: Vec<Line>) -> Self {
+        Self::new(walls, DirtMap::new(Float2::ZERO, Float2::ZERO, 100.0))
+    }
+
+    // don't query the world again while holding the guard,
+    // a waiting writer may block the second read
+    pub fn geometry(&self) -> RwLockReadGuard<'_, Geometry> {
+        self.geometry.read().unwrap()
+    }
+
+    // returns the index for set_wall
+    pub fn add_wall(&self, wall: Line) -> usize {
+        self.geometry.write().unwrap().add_wall(wall)
+    }
+
+    // e.g. a door swinging or a chair pushed aside
+    pub fn set_wall(&self, index: usize, wall: Line) {
+        self.geometry.write().unwrap().set_wall(index, wall);
+    }
+}
+
+// walls are looked up through the grid, moving one only updates the cells it touches
+// the distance field answers clearance queries away from the walls without touching them
+pub struct Geometry {
+    walls: Vec<Line>,
+    arrays: WallArrays,
+    grid: Grid,
+    field: DistanceField,
+}
+
+impl Geometry {
+    fn new(walls: Vec<Line>) -> Self {
+        Self {
+            grid: Grid::new(&walls, GRID_CELL_SIZE),
+            field: DistanceField::new(&walls, FIELD_CELL_SIZE, FIELD_MARGIN, FIELD_BAND),
+            arrays: WallArrays::new(&walls),
+            walls,
+        }
+    }
+
+    pub fn walls(&self) -> &[Line] {
+        &self.walls
+    }
+
+    pub fn get_grid(&self) -> &Grid {
+        &self.grid
+    }
+
+    pub fn get_arrays(&self) -> &WallArrays {
+        &self.arrays
+    }
+
+    fn add_wall(&mut self, wall: Line) -> usize {
+        let index = self.walls.len();
+        self.grid.insert(index, &wall);
+        self.arrays.push(&wall);
+        self.field.insert(&wall);
+        self.walls.push(wall);
+        index
+    }
+
+    // only the samples within the band of the old and the new position get touched
+    fn set_wall(&mut self, index: usize, wall: Line) {
+        let old = std::mem::replace(&mut self.walls[index], wall);
+        self.grid.update(index, &old, &wall);
+        self.arrays.set(index, &wall);
+        let band = self.field.get_band();
+        let (walls, grid) = (&self.walls, &self.grid);
+        self.field.remove(&old, |node| {
+            grid.query_radius(node, band)
+                .into_iter()
+                .map(|index| walls[index].distance_to(node))
+                .fold(band, f32::min)
+        });
+        self.field.insert(&wall);
+    }
+
+    // walls that may come closer than radius to the center
+    pub fn walls_near(&self, center: Float2, radius: f32) -> impl Iterator<Item = &Line> + '_ {
+        self.grid
+            .query_radius(center, radius)
+            .into_iter()
+            .map(|index| &self.walls[index])
+    }
+
+    // closest wall and its distance, exact
+    // only walls within radius are searched, outside of the field all of them
+    fn nearest_wall(&self, point: Float2, radius: Option<f32>) -> Option<(f32, &Line)> {
+        let walls: Box<dyn Iterator<Item = &Line>> = match radius {
+            Some(radius) => Box::new(self.walls_near(point, radius)),
+            None => Box::new(self.walls.iter()),
+        };
+        walls
+            .map(|wall| (wall.distance_to(point), wall))
+            .min_by(|(a, _), (b, _)| a.total_cmp(b))
+    }
+
+    // the field is coarse close to the walls and knows nothing beyond the band,
+    // there the closest wall gets searched: Err(radius), None searches every wall
+    // elsewhere Ok(sample)
+    fn refine(&self, point: Float2) -> Result<f32, Option<f32>> {
+        let error = self.field.max_error();
+        match self.field.sample(point) {
+            Some(sample) if sample + error >= self.field.get_band() => Err(None),
+            Some(sample) if sample > error => Ok(sample),
+            Some(sample) => Err(Some(sample + error)),
+            None => Err(None),
+        }
+    }
+
+    // distance from the point to the closest wall in mm, of any height
+    // within the band the sampled value, close to the walls and beyond the band the exact one
+    pub fn distance_at(&self, point: Float2) -> f32 {
+        match self.refine(point) {
+            Ok(sample) => sample,
+            Err(radius) => self
+                .nearest_wall(point, radius)
+                .map_or(f32::INFINITY, |(distance, _)| distance),
+        }
+    }
+
+    // direction in which the distance grows fastest, away from the closest wall
+    // within the band the gradient of the sampled field, elsewhere the exact direction
+    // None on a wall or without any walls
+    #[allow(dead_code)]
+    pub fn gradient_at(&self, point: Float2) -> Option<Float2> {
+        match self.refine(point) {
+            Ok(_) => self.field.gradient(point),
+            Err(radius) => {
+                let (_, wall) = self.nearest_wall(point, radius)?;
+                (point - wall.closest_point(point)).normalize()
+            }
+        }
+    }
+
+    // does a circle touch a wall reaching into the height range?
+    // away from the walls the distance field alone rules it out
+    pub fn check_collision(
+        &self,
+        center: Float2,
+        radius: Length,
+        z_min: Length,
+        z_max: Length,
+    ) -> bool {
+        let clear = self
+            .field
+            .sample(center)
+            .is_some_and(|sample| sample - self.field.max_error() > radius.mm());
+        !clear
+            && self
+                .walls_near(center, radius.mm())
+                .filter(|wall| wall.overlaps(z_min, z_max))
+                .any(|wall| circle_intersects_line(center, radius, *wall))
+    }
+
+    // fraction 0.0..=1.0 of the motion at which a moving circle first touches a wall
+    // reaching into the height range, None if it gets through, independent of the step length
+    pub fn sweep_circle(
+        &self,
+        center: Float2,
+        motion: Float2,
+        radius: Length,
+        z_min: Length,
+        z_max: Length,
+    ) -> Option<f32> {
+        let reach = radius.mm() + motion.length();
+        let clear = self
+            .field
+            .sample(center)
+            .is_some_and(|sample| sample - self.field.max_error() > reach);
+        if clear {
+            return None;
+        }
+        self.walls_near(center + motion / 2.0, radius.mm() + motion.length() / 2.0)
+            .filter(|wall| wall.overlaps(z_min, z_max))
+            .filter_map(|wall| wall.sweep_circle(center, motion, radius.mm()))
+            .min_by(f32::total_cmp)
+    }
+
+    // same for any footprint, e.g. a square robot or a carried object
+    #[allow(dead_code)]
+    pub fn sweep_polygon(
+        &self,
+        footprint: &Polygon,
+        motion: Float2,
+        z_min: Length,
+        z_max: Length,
+    ) -> Option<f32> {
+        let points = footprint.get_points();
+        let center = points.iter().fold(Float2::ZERO, |sum, point| sum + *point)
+            / points.len().max(1) as f32;
+        let radius = points
+            .iter()
+            .map(|point| point.distance(center))
+            .fold(0.0, f32::max);
+        self.walls_near(center + motion / 2.0, radius + motion.length() / 2.0)
+            .filter(|wall| wall.overlaps(z_min, z_max))
+            .filter_map(|wall| footprint.sweep(motion, wall))
+            .min_by(f32::total_cmp)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::sync::Arc;
+    use std::thread;
+
+    #[test]
+    fn walls_move_while_the_world_is_shared() {
+        let corner = Line::new(Float2::new(2000.0, 0.0), Float2::new(2000.0, 2000.0));
+        let door = Line::new(Float2::new(0.0, 0.0), Float2::new(0.0, 1000.0));
+        let world = Arc::new(World::from_walls(vec![corner, door]));
+        let reader = Arc::clone(&world);
+
+        let opened = Line::new(Float2::new(0.0, 0.0), Float2::new(1000.0, 0.0));
+        thread::spawn(move || world.set_wall(1, opened))
+            .join()
+            .unwrap();
+
+        let geometry = reader.geometry();
+        assert_eq!(geometry.walls(), &[corner, opened]);
+        assert_eq!(
+            geometry.walls_near(Float2::new(0.0, 800.0), 10.0).count(),
+            0
+        );
+        assert_eq!(
+            geometry.walls_near(Float2::new(800.0, 0.0), 10.0).count(),
+            1
+        );
+    }
+
+    // distance to the closest wall of any height, testing every wall
+    fn brute_force(walls: &[Line], point: Float2) -> f32 {
+        walls
+            .iter()
+            .map(|wall| wall.distance_to(point))
+            .fold(f32::INFINITY, f32::min)
+    }
+
+    #[test]
+    fn distance_matches_testing_every_wall() {
+        let mut walls = vec![
+            Line::new(Float2::new(0.0, 0.0), Float2::new(4000.0, 0.0)),
+            Line::new(Float2::new(4000.0, 0.0), Float2::new(4000.0, 3000.0)),
+            Line::new(Float2::new(1000.0, 1000.0), Float2::new(1000.0, 1800.0)),
+        ];
+        let world = World::from_walls(walls.clone());
+        let opened = Line::new(Float2::new(1000.0, 1000.0), Float2::new(1800.0, 1000.0));
+        world.set_wall(2, opened);
+        walls[2] = opened;
+
+        let geometry = world.geometry();
+        let error = FIELD_CELL_SIZE * std::f32::consts::SQRT_2;
+        // close to the walls, within the band and far outside the field
+        for point in [
+            Float2::new(1000.0, 1010.0),
+            Float2::new(1005.0, 1600.0),
+            Float2::new(3990.0, 1500.0),
+            Float2::new(1300.0, 1200.0),
+            Float2::new(2500.0, 1900.0),
+            Float2::new(1900.0, 2800.0),
+            Float2::new(-3000.0, 5000.0),
+        ] {
+            let exact = brute_force(&walls, point);
+            let distance = geometry.distance_at(point);
+            assert!((distance - exact).abs() <= error, "{point:?}");
+            if exact < error || exact > FIELD_BAND {
+                assert_eq!(distance, exact, "{point:?}");
+            }
+        }
+    }
+
+    #[test]
+    fn gradient_matches_the_change_of_the_distance() {
+        let world = World::from_walls(vec![
+            Line::new(Float2::new(0.0, 0.0), Float2::new(4000.0, 0.0)),
+            Line::new(Float2::new(4000.0, 0.0), Float2::new(4000.0, 3000.0)),
+            Line::new(Float2::new(1000.0, 1000.0), Float2::new(1800.0, 1000.0)),
+        ]);
+        let geometry = world.geometry();
+        let step = 0.5;
+        // sampled in the middle of cells, close to the walls, beyond the band and the field
+        for point in [
+            Float2::new(2525.0, 225.0),
+            Float2::new(3775.0, 1525.0),
+            Float2::new(1925.0, 1125.0),
+            Float2::new(1300.0, 1030.0),
+            Float2::new(3990.0, 1500.0),
+            Float2::new(1900.0, 2800.0),
+            Float2::new(-3000.0, 5000.0),
+        ] {
+            let change = |offset: Float2| {
+                geometry.distance_at(point + offset) - geometry.distance_at(point - offset)
+            };
+            let expected = Float2::new(
+                change(Float2::new(step, 0.0)),
+                change(Float2::new(0.0, step)),
+            ) / (2.0 * step);
+            let gradient = geometry.gradient_at(point).unwrap();
+            assert!(gradient.approx_eq(expected, 1e-2), "{point:?}");
+        }
+        assert_eq!(geometry.gradient_at(Float2::new(2000.0, 0.0)), None);
+    }
+
+    #[test]
+    fn sweeps_stop_at_walls_within_the_height() {
+        let low = Line::with_height(
+            Float2::new(1000.0, 0.0),
+            Float2::new(1000.0, 2000.0),
+            Length::ZERO,
+            Length::from_mm(50.0),
+        );
+        let world = World::from_walls(vec![low]);
+        let geometry = world.geometry();
+        let motion = Float2::new(1000.0, 0.0);
+        let (z_min, z_max) = (Length::from_mm(10.0), Length::from_mm(70.0));
+
+        let t = geometry
+            .sweep_circle(
+                Float2::new(400.0, 1000.0),
+                motion,
+                Length::from_mm(100.0),
+                z_min,
+                z_max,
+            )
+            .unwrap();
+        assert!((t - 0.5).abs() < 1e-6);
+        // passes over the wall
+        let above = (Length::from_mm(60.0), Length::from_mm(90.0));
+        assert_eq!(
+            geometry.sweep_circle(
+                Float2::new(400.0, 1000.0),
+                motion,
+                Length::from_mm(100.0),
+                above.0,
+                above.1
+            ),
+            None
+        );
+
+        let footprint = Polygon::rectangle(Float2::new(300.0, 900.0), Float2::new(500.0, 1100.0));
+        assert_eq!(
+            geometry.sweep_polygon(&footprint, motion, z_min, z_max),
+            Some(0.5)
+        );
+        assert_eq!(
+            geometry.sweep_polygon(&footprint, motion, above.0, above.1),
+            None
+        );
+    }
+}